@@ -0,0 +1,203 @@
+//! Compares the peeking group parsers with the backtracking ones they replaced, run with
+//! `cargo bench --bench dispatch [-- <containers>]`.
+//!
+//! The backtracking parsers are written the way `ParseSg` expanded before: every field runs the
+//! child parser on the text through `nom::multi::many0` and `nom::combinator::opt`, an absent
+//! segment costs a failed segment parse. The peeking ones are the derived `parse`, which splits
+//! the text into segments once and looks at the next tag before calling a child parser.
+#![allow(clippy::upper_case_acronyms)]
+
+#[path = "../tests/util/mod.rs"]
+mod util;
+
+use edifact_types_macros::Edifact;
+use nom::combinator::opt;
+use nom::multi::many0;
+use nom::IResult;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use util::{EdifactError, Parser};
+
+macro_rules! seg {
+    ($($n:ident),*) => {$(
+        #[derive(Debug, Clone, Default, PartialEq, Edifact)]
+        #[edifact(segment)]
+        pub struct $n {
+            pub _010: Option<String>,
+            pub _020: Option<String>,
+            pub _030: Option<String>,
+        }
+    )*};
+}
+seg!(BGM, CNI, LOC, DTM, STS, RFF, FTX, NAD, EQD, MEA, SEL, CTA, COM);
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg4 {
+    pub eqd: EQD,
+    pub mea: Vec<MEA>,
+    pub sel: Vec<SEL>,
+    pub ftx: Option<FTX>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg5 {
+    pub nad: NAD,
+    pub cta: Option<CTA>,
+    pub com: Vec<COM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg3 {
+    pub sts: STS,
+    pub rff: Vec<RFF>,
+    pub dtm: Vec<DTM>,
+    pub ftx: Vec<FTX>,
+    pub nad: Vec<NAD>,
+    pub loc: Option<LOC>,
+    pub sg4: Vec<Sg4>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub cni: CNI,
+    pub loc: Vec<LOC>,
+    pub cta: Vec<CTA>,
+    pub dtm: Vec<DTM>,
+    pub rff: Vec<RFF>,
+    pub sg5: Vec<Sg5>,
+    pub sg3: Vec<Sg3>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Iftsta {
+    pub bgm: BGM,
+    pub dtm: Vec<DTM>,
+    pub ftx: Vec<FTX>,
+    pub sg5: Vec<Sg5>,
+    pub sg1: Vec<Sg1>,
+}
+
+type Res<'a, T> = IResult<&'a str, T, EdifactError>;
+
+fn sg4(i: &str) -> Res<'_, Sg4> {
+    let (i, eqd) = EQD::parse(i)?;
+    let (i, mea) = many0(MEA::parse)(i)?;
+    let (i, sel) = many0(SEL::parse)(i)?;
+    let (i, ftx) = opt(FTX::parse)(i)?;
+    Ok((i, Sg4 { eqd, mea, sel, ftx }))
+}
+
+fn sg5(i: &str) -> Res<'_, Sg5> {
+    let (i, nad) = NAD::parse(i)?;
+    let (i, cta) = opt(CTA::parse)(i)?;
+    let (i, com) = many0(COM::parse)(i)?;
+    Ok((i, Sg5 { nad, cta, com }))
+}
+
+fn sg3(i: &str) -> Res<'_, Sg3> {
+    let (i, sts) = STS::parse(i)?;
+    let (i, rff) = many0(RFF::parse)(i)?;
+    let (i, dtm) = many0(DTM::parse)(i)?;
+    let (i, ftx) = many0(FTX::parse)(i)?;
+    let (i, nad) = many0(NAD::parse)(i)?;
+    let (i, loc) = opt(LOC::parse)(i)?;
+    let (i, sg4) = many0(sg4)(i)?;
+    let sg = Sg3 {
+        sts,
+        rff,
+        dtm,
+        ftx,
+        nad,
+        loc,
+        sg4,
+    };
+    Ok((i, sg))
+}
+
+fn sg1(i: &str) -> Res<'_, Sg1> {
+    let (i, cni) = CNI::parse(i)?;
+    let (i, loc) = many0(LOC::parse)(i)?;
+    let (i, cta) = many0(CTA::parse)(i)?;
+    let (i, dtm) = many0(DTM::parse)(i)?;
+    let (i, rff) = many0(RFF::parse)(i)?;
+    let (i, sg5) = many0(sg5)(i)?;
+    let (i, sg3) = many0(sg3)(i)?;
+    let sg = Sg1 {
+        cni,
+        loc,
+        cta,
+        dtm,
+        rff,
+        sg5,
+        sg3,
+    };
+    Ok((i, sg))
+}
+
+fn backtracking(i: &str) -> Res<'_, Iftsta> {
+    let (i, bgm) = BGM::parse(i)?;
+    let (i, dtm) = many0(DTM::parse)(i)?;
+    let (i, ftx) = many0(FTX::parse)(i)?;
+    let (i, sg5) = many0(sg5)(i)?;
+    let (i, sg1) = many0(sg1)(i)?;
+    let msg = Iftsta {
+        bgm,
+        dtm,
+        ftx,
+        sg5,
+        sg1,
+    };
+    Ok((i, msg))
+}
+
+/// A status message with `n` containers, three events each.
+fn message(n: usize) -> String {
+    let mut s = String::from("BGM+23+4711+9'\nDTM+137:20240101:102'\nNAD+CZ+SHIPPER'\n");
+    for i in 0..n {
+        s.push_str(&format!(
+            "CNI+{i}'\nLOC+5+DEHAM'\nDTM+7:202401011200:203'\n"
+        ));
+        for j in 0..3 {
+            s.push_str(&format!(
+                "STS+1+{j}'\nRFF+BN:ABC{i}'\nDTM+334:20240101'\nLOC+175+NLRTM'\nEQD+CN+MSKU{i}'\nMEA+AAE+G+KGM:1000'\nSEL+123'\n"
+            ));
+        }
+    }
+    s
+}
+
+/// Runs `f` for at least a second and returns the best throughput in MB/s.
+fn measure(input: &str, f: impl Fn(&str) -> Iftsta) -> f64 {
+    let mut best = Duration::MAX;
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(1) {
+        let t = Instant::now();
+        black_box(f(black_box(input)));
+        best = best.min(t.elapsed());
+    }
+    input.len() as f64 / best.as_secs_f64() / 1e6
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` along
+    let n = std::env::args()
+        .skip(1)
+        .find_map(|a| a.parse().ok())
+        .unwrap_or(5_000);
+    let input = message(n);
+    let (rest, peeking) = Iftsta::parse(&input).unwrap();
+    assert_eq!(rest, "");
+    let (rest, old) = backtracking(&input).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(peeking, old);
+    println!("{} containers, {} bytes", n, input.len());
+    let peek = measure(&input, |i| Iftsta::parse(i).unwrap().1);
+    println!("peek dispatch  {peek:8.1} MB/s");
+    let back = measure(&input, |i| backtracking(i).unwrap().1);
+    println!("backtracking   {back:8.1} MB/s");
+}
@@ -0,0 +1,171 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+/// What a struct deriving `Edifact` stands for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Structure {
+    /// `segment`, a tagged segment with data elements
+    Segment,
+    /// `composite`, a composite data element inside a segment
+    Composite,
+    /// `group`, a segment group
+    Group,
+    /// `message`, a message, optionally starting with a UNA segment
+    Message,
+}
+
+/// What a message does with segments it does not model.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Unknown {
+    /// `unknown = "reject"`, fails at the first unknown segment
+    Reject,
+    /// `unknown = "skip"`, leaves them out and reports them to the `ParseContext`
+    Skip,
+}
+
+/// Struct options given with `#[edifact(..)]`.
+pub(crate) struct ContainerAttrs {
+    /// `crate = "edifact_types"`, the crate providing the `util` module, `crate` by default
+    pub krate: syn::Path,
+    /// `segment`, `composite`, `group` or `message`, only used by `Edifact`
+    pub structure: Option<Structure>,
+    /// `tag = "NAD"`, the segment tag, the uppercased struct name by default
+    pub tag: Option<String>,
+    /// `qualifier = "BY"`, the segment is only accepted with this code in its first element
+    pub qualifier: Option<String>,
+    /// `no_into_owned`, leaves out `into_owned` of a borrowing struct, e.g. for groups of segments with `&'a str` fields
+    pub no_into_owned: bool,
+    /// `unknown = "reject"` or `unknown = "skip"`, the policy of a message for segments it does not model
+    pub unknown: Option<Unknown>,
+}
+
+impl ContainerAttrs {
+    pub fn from_ast(ast: &DeriveInput) -> syn::Result<ContainerAttrs> {
+        let mut krate = None;
+        let mut structure = None;
+        let mut tag = None;
+        let mut qualifier = None;
+        let mut no_into_owned = false;
+        let mut unknown = None;
+        for attr in ast.attrs.iter().filter(|a| a.path().is_ident("edifact")) {
+            attr.parse_nested_meta(|meta| {
+                let found = if meta.path.is_ident("crate") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    krate = Some(lit.parse::<syn::Path>()?);
+                    return Ok(());
+                } else if meta.path.is_ident("tag") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    tag = Some(lit.value());
+                    return Ok(());
+                } else if meta.path.is_ident("qualifier") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    qualifier = Some(lit.value());
+                    return Ok(());
+                } else if meta.path.is_ident("unknown") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    unknown = Some(match lit.value().as_str() {
+                        "reject" => Unknown::Reject,
+                        "skip" => Unknown::Skip,
+                        _ => return Err(syn::Error::new_spanned(lit, "expected reject or skip")),
+                    });
+                    return Ok(());
+                } else if meta.path.is_ident("no_into_owned") {
+                    no_into_owned = true;
+                    return Ok(());
+                } else if meta.path.is_ident("segment") {
+                    Structure::Segment
+                } else if meta.path.is_ident("composite") {
+                    Structure::Composite
+                } else if meta.path.is_ident("group") {
+                    Structure::Group
+                } else if meta.path.is_ident("message") {
+                    Structure::Message
+                } else {
+                    return Err(meta.error("unsupported edifact attribute"));
+                };
+                if structure.is_some() {
+                    return Err(
+                        meta.error("only one of segment, composite, group and message is allowed")
+                    );
+                }
+                structure = Some(found);
+                Ok(())
+            })?;
+        }
+        Ok(ContainerAttrs {
+            krate: krate.unwrap_or_else(|| syn::parse_quote!(crate)),
+            structure,
+            tag,
+            qualifier,
+            no_into_owned,
+            unknown,
+        })
+    }
+}
+
+/// Path of the `util` module the generated code calls into, e.g. `::edifact_types::util`.
+pub(crate) fn util_path(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let krate = ContainerAttrs::from_ast(ast)?.krate;
+    Ok(quote! { #krate::util })
+}
+
+/// The segment tag, given with `#[edifact(tag = "NAD")]` or the uppercased struct name.
+///
+/// Several structs can share a tag, e.g. `NadBuyer` and `NadSeller` both with `tag = "NAD"`.
+pub(crate) fn segment_tag(ast: &DeriveInput) -> syn::Result<String> {
+    Ok(ContainerAttrs::from_ast(ast)?
+        .tag
+        .unwrap_or_else(|| ast.ident.to_string().to_uppercase()))
+}
+
+/// How the generated parsers name the lifetime of their input.
+///
+/// A struct with a lifetime parameter borrows from the input, its parsers take input of that
+/// lifetime. Parsers of owned structs declare a lifetime `'a` of their own.
+pub(crate) struct Input {
+    /// the lifetime of the input
+    pub lt: syn::Lifetime,
+    /// `true` if the struct has a lifetime parameter
+    pub borrowed: bool,
+    /// generics of the inherent impls, `<'a>` for a borrowing struct
+    pub impl_generics: TokenStream,
+    /// lifetimes the methods declare themselves, `'a,` for an owned struct
+    pub fn_lts: TokenStream,
+    /// the struct type, `NAD` or `NAD<'a>`
+    pub ty: TokenStream,
+}
+
+pub(crate) fn input(ast: &DeriveInput) -> syn::Result<Input> {
+    let name = &ast.ident;
+    let generics = &ast.generics;
+    let lifetimes: Vec<_> = generics.lifetimes().collect();
+    if lifetimes.len() > 1 || generics.params.len() > lifetimes.len() {
+        return Err(syn::Error::new_spanned(
+            generics,
+            "only a single lifetime parameter is supported",
+        ));
+    }
+    Ok(match lifetimes.first() {
+        Some(param) => {
+            let lt = param.lifetime.clone();
+            Input {
+                impl_generics: quote! { <#lt> },
+                fn_lts: quote! {},
+                ty: quote! { #name<#lt> },
+                lt,
+                borrowed: true,
+            }
+        }
+        None => {
+            let lt: syn::Lifetime = syn::parse_quote!('a);
+            Input {
+                impl_generics: quote! {},
+                fn_lts: quote! { #lt, },
+                ty: quote! { #name },
+                lt,
+                borrowed: false,
+            }
+        }
+    })
+}
@@ -0,0 +1,339 @@
+use proc_macro2::Ident;
+use syn::{Data, DeriveInput, GenericArgument, PathArguments, Type};
+
+/// Field options given with `#[edifact(..)]`.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// `una`, the UNA segment of a message
+    pub una: bool,
+    /// `max_repeat = 5`, upper bound for a repeating data element
+    pub max_repeat: Option<usize>,
+    /// `text`, `code` or `composite`, overrides the classification by type
+    pub kind: Option<Kind>,
+    /// `qualifier = "BY"`, a segment in a group only matching with this qualifier
+    pub qualifier: Option<String>,
+    /// `trigger`, the segment or group starting a group, the first field by default
+    pub trigger: bool,
+    /// `min = 1`, least number of occurrences of a repeated segment or group
+    pub min: Option<usize>,
+    /// `max = 99`, most number of occurrences of a repeated segment or group
+    pub max: Option<usize>,
+    /// `rest`, the surplus elements or components of a segment or composite, still escaped
+    pub rest: bool,
+    /// `span`, the position of a segment, group or message in the input, not an element
+    pub span: bool,
+    /// `unknown`, the segments of a message it does not model, as `Vec<RawSegment<'a>>`
+    pub unknown: bool,
+}
+
+impl FieldAttrs {
+    pub fn from_attrs(attributes: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+        let mut attrs = FieldAttrs::default();
+        for attr in attributes.iter().filter(|a| a.path().is_ident("edifact")) {
+            attr.parse_nested_meta(|meta| {
+                let kind = if meta.path.is_ident("una") {
+                    attrs.una = true;
+                    None
+                } else if meta.path.is_ident("max_repeat") {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    attrs.max_repeat = Some(lit.base10_parse()?);
+                    None
+                } else if meta.path.is_ident("qualifier") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.qualifier = Some(lit.value());
+                    None
+                } else if meta.path.is_ident("trigger") {
+                    attrs.trigger = true;
+                    None
+                } else if meta.path.is_ident("min") {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    attrs.min = Some(lit.base10_parse()?);
+                    None
+                } else if meta.path.is_ident("max") {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    attrs.max = Some(lit.base10_parse()?);
+                    None
+                } else if meta.path.is_ident("rest") {
+                    attrs.rest = true;
+                    None
+                } else if meta.path.is_ident("span") {
+                    attrs.span = true;
+                    None
+                } else if meta.path.is_ident("unknown") {
+                    attrs.unknown = true;
+                    None
+                } else if meta.path.is_ident("text") {
+                    Some(Kind::Text)
+                } else if meta.path.is_ident("code") {
+                    Some(Kind::Code)
+                } else if meta.path.is_ident("composite") {
+                    Some(Kind::Composite)
+                } else {
+                    return Err(meta.error("unsupported edifact attribute"));
+                };
+                if let Some(kind) = kind {
+                    if attrs.kind.is_some() {
+                        return Err(meta.error("only one of text, code and composite is allowed"));
+                    }
+                    attrs.kind = Some(kind);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// How a field wraps its value.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Shape {
+    /// `T`, mandatory
+    Plain,
+    /// `Option<T>`, conditional
+    Option,
+    /// `Vec<T>`, repeating
+    Vec,
+}
+
+/// What kind of value sits in an element or component position.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// free text, held as given by `Text`
+    Text,
+    /// code list entry, converted with `FromStr` and `Display`
+    Code,
+    /// composite with derived `parse_with` and `fmt_with`
+    Composite,
+    /// anything else, dispatched through `util::ParseValue` and `util::DisplayValue`
+    Value,
+}
+
+/// How a text field holds its value.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Text {
+    /// `String`, unescaped
+    Owned,
+    /// `&'a str`, the slice of the input, values with a release character are rejected
+    Borrowed,
+    /// `Cow<'a, str>`, unescaped, only allocating when there is a release character
+    Cow,
+}
+
+/// A named struct field with its shape, inner type and kind.
+pub(crate) struct EdiField {
+    pub ident: Ident,
+    pub shape: Shape,
+    /// the type inside `Option` or `Vec`, the field type otherwise
+    pub ty: Type,
+    pub kind: Kind,
+    /// only meaningful for `Kind::Text`
+    pub text: Text,
+    pub attrs: FieldAttrs,
+}
+
+impl EdiField {
+    pub fn from_field(field: &syn::Field) -> syn::Result<EdiField> {
+        let Some(ident) = field.ident.clone() else {
+            return Err(syn::Error::new_spanned(
+                field,
+                "only named fields are supported",
+            ));
+        };
+        let attrs = FieldAttrs::from_attrs(&field.attrs)?;
+        let (shape, ty) = match wrapped(&field.ty, "Option") {
+            Some(ty) => (Shape::Option, ty.clone()),
+            None => match wrapped(&field.ty, "Vec") {
+                Some(ty) => (Shape::Vec, ty.clone()),
+                None => (Shape::Plain, field.ty.clone()),
+            },
+        };
+        let text = if is_str(&ty) {
+            Text::Borrowed
+        } else if wrapped(&ty, "Cow").is_some_and(is_str_path) {
+            Text::Cow
+        } else {
+            Text::Owned
+        };
+        let kind = match attrs.kind {
+            Some(kind) => kind,
+            None if text != Text::Owned || is_string(&ty) => Kind::Text,
+            None => Kind::Value,
+        };
+        Ok(EdiField {
+            ident,
+            shape,
+            ty,
+            kind,
+            text,
+            attrs,
+        })
+    }
+
+    /// Label in the error path, the type name for composites and code lists, the position for text.
+    ///
+    /// `_3036` becomes `3036`, `C080` stays `C080` and a `_010: String` becomes `010`.
+    pub fn label(&self) -> String {
+        let label = match (&self.ty, self.kind) {
+            (Type::Path(p), Kind::Code | Kind::Composite | Kind::Value) => {
+                p.path.segments.last().unwrap().ident.to_string()
+            }
+            _ => self.ident.to_string(),
+        };
+        label.trim_start_matches('_').to_string()
+    }
+}
+
+/// A variant of a choice enum, wrapping a single segment or group.
+pub(crate) struct EdiVariant {
+    pub ident: Ident,
+    pub ty: Type,
+    pub attrs: FieldAttrs,
+}
+
+impl EdiVariant {
+    pub fn from_variant(variant: &syn::Variant) -> syn::Result<EdiVariant> {
+        let ty = match &variant.fields {
+            syn::Fields::Unnamed(f) if f.unnamed.len() == 1 => f.unnamed[0].ty.clone(),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "only variants wrapping a single segment or group are supported",
+                ))
+            }
+        };
+        Ok(EdiVariant {
+            ident: variant.ident.clone(),
+            ty,
+            attrs: FieldAttrs::from_attrs(&variant.attrs)?,
+        })
+    }
+}
+
+/// The variants of a choice enum, `None` for structs.
+pub(crate) fn variants(ast: &DeriveInput) -> syn::Result<Option<Vec<EdiVariant>>> {
+    match &ast.data {
+        Data::Enum(e) => e
+            .variants
+            .iter()
+            .map(EdiVariant::from_variant)
+            .collect::<syn::Result<_>>()
+            .map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The named fields of a struct, in declaration order, without the `span` and `unknown` fields.
+pub(crate) fn fields(ast: &DeriveInput) -> syn::Result<Vec<EdiField>> {
+    let fields: Vec<EdiField> = all_fields(ast)?
+        .into_iter()
+        .filter(|f| !f.attrs.span && !f.attrs.unknown)
+        .collect();
+    // the surplus values come after all the others
+    if let Some(pos) = fields.iter().position(|f| f.attrs.rest) {
+        let field = &fields[pos];
+        if pos + 1 != fields.len() || field.shape != Shape::Vec {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                "rest has to be the last field and a Vec",
+            ));
+        }
+    }
+    Ok(fields)
+}
+
+/// The field marked with `#[edifact(span)]`, at most one per struct.
+pub(crate) fn span_field(ast: &DeriveInput) -> syn::Result<Option<Ident>> {
+    let mut spans = all_fields(ast)?.into_iter().filter(|f| f.attrs.span);
+    let span = spans.next().map(|f| f.ident);
+    if let Some(other) = spans.next() {
+        return Err(syn::Error::new_spanned(
+            other.ident,
+            "only one span field per struct is allowed",
+        ));
+    }
+    Ok(span)
+}
+
+/// The `Vec` marked with `#[edifact(unknown)]`, at most one per struct.
+pub(crate) fn unknown_field(ast: &DeriveInput) -> syn::Result<Option<Ident>> {
+    let mut unknown = all_fields(ast)?.into_iter().filter(|f| f.attrs.unknown);
+    let Some(field) = unknown.next() else {
+        return Ok(None);
+    };
+    if let Some(other) = unknown.next() {
+        return Err(syn::Error::new_spanned(
+            other.ident,
+            "only one unknown field per struct is allowed",
+        ));
+    }
+    if field.shape != Shape::Vec {
+        return Err(syn::Error::new_spanned(
+            field.ident,
+            "unknown has to be a Vec of RawSegment",
+        ));
+    }
+    Ok(Some(field.ident))
+}
+
+fn all_fields(ast: &DeriveInput) -> syn::Result<Vec<EdiField>> {
+    match &ast.data {
+        Data::Struct(s) => s.fields.iter().map(EdiField::from_field).collect(),
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "only structs with named fields are supported",
+        )),
+    }
+}
+
+/// Returns `T` if `ty` is `wrapper<T>`, also for qualified paths like `std::option::Option<T>`.
+fn wrapped<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let last = p.path.segments.last()?;
+    if last.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    // `Cow<'a, str>` starts with its lifetime
+    args.args.iter().find_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+/// `String` or `std::string::String`.
+fn is_string(ty: &Type) -> bool {
+    matches!(ty, Type::Path(p) if p.qself.is_none()
+        && p.path.segments.last().is_some_and(|s| s.ident == "String" && s.arguments.is_none()))
+}
+
+/// `&str` with any lifetime.
+fn is_str(ty: &Type) -> bool {
+    matches!(ty, Type::Reference(r) if r.mutability.is_none() && is_str_path(&r.elem))
+}
+
+/// `str`, the inner type of `&'a str` and `Cow<'a, str>`.
+fn is_str_path(ty: &Type) -> bool {
+    matches!(ty, Type::Path(p) if p.qself.is_none() && p.path.is_ident("str"))
+}
+
+/// Whether `ty` borrows, i.e. is a reference or has a lifetime argument somewhere.
+pub(crate) fn has_lifetime(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(_) => true,
+        Type::Paren(p) => has_lifetime(&p.elem),
+        Type::Group(g) => has_lifetime(&g.elem),
+        Type::Path(p) => p.path.segments.iter().any(|s| match &s.arguments {
+            PathArguments::AngleBracketed(args) => args.args.iter().any(|a| match a {
+                GenericArgument::Lifetime(_) => true,
+                GenericArgument::Type(ty) => has_lifetime(ty),
+                _ => false,
+            }),
+            _ => false,
+        }),
+        _ => false,
+    }
+}
@@ -1,31 +1,139 @@
 extern crate proc_macro;
 
-use proc_macro2::{Ident, Span, TokenStream};
+mod container;
+mod field;
+
+use container::{input, segment_tag, util_path, ContainerAttrs, Input, Structure, Unknown};
+use field::{
+    fields, has_lifetime, span_field, unknown_field, variants, EdiField, EdiVariant, FieldAttrs,
+    Kind, Shape, Text,
+};
+use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, PathArguments, Type};
+use syn::{parse_macro_input, DeriveInput};
+
+/// Generates `Parser`, `FromStr` and `Display` for a segment, composite, group or message.
+///
+/// The struct kind is given with `#[edifact(segment)]`, `#[edifact(composite)]`,
+/// `#[edifact(group)]` or `#[edifact(message)]`.
+#[proc_macro_derive(Edifact, attributes(edifact))]
+pub fn edifact(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    let toks = generate_unified(&ast).unwrap_or_else(|err| err.to_compile_error());
+    #[cfg(feature = "debug")]
+    println!("{toks}");
+    toks.into()
+}
+
+/// All impls walk the same field model, `FromStr` runs the parser so both accept the same input.
+fn generate_unified(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let attrs = ContainerAttrs::from_ast(ast)?;
+    let Some(structure) = attrs.structure else {
+        return Err(syn::Error::new_spanned(
+            &ast.ident,
+            "expected one of #[edifact(segment)], #[edifact(composite)], #[edifact(group)] or #[edifact(message)]",
+        ));
+    };
+    if (attrs.tag.is_some() || attrs.qualifier.is_some()) && structure != Structure::Segment {
+        return Err(syn::Error::new_spanned(
+            &ast.ident,
+            "only segments have a tag and a qualifier",
+        ));
+    }
+    let (parser, display) = match structure {
+        Structure::Segment => (generate_segment_parser(ast)?, generate_outer_display(ast)?),
+        Structure::Composite => (generate_element_parser(ast)?, generate_inner_display(ast)?),
+        Structure::Group => (generate_sg_parser(ast, true)?, generate_edifact_sg(ast)?),
+        Structure::Message => (generate_sg_parser(ast, false)?, generate_edifact(ast)?),
+    };
+    // `FromStr` cannot hand out values borrowing from its argument
+    let from_str = if input(ast)?.borrowed {
+        quote! {}
+    } else {
+        gen_from_str(ast, structure)?
+    };
+    Ok(quote! {
+        #parser
+        #display
+        #from_str
+    })
+}
+
+/// Implements `FromStr` on top of `parse_with`, anything but whitespace left over is an error.
+///
+/// Like the separate `FromStr` derives it is strict, surplus elements and components are rejected.
+fn gen_from_str(ast: &DeriveInput, structure: Structure) -> syn::Result<TokenStream> {
+    let util = util_path(ast)?;
+    let name = &ast.ident;
+    let label = match structure {
+        Structure::Segment => segment_tag(ast)?,
+        _ => name.to_string().to_uppercase(),
+    };
+    let seps = match structure {
+        Structure::Message => quote! { #util::Separators::from_una(s).unwrap_or_default() },
+        _ => quote! { #util::Separators::default() },
+    };
+    // `Display` of a segment leaves out the terminator, the parser needs it. A terminator after an odd
+    // number of release characters belongs to the last value.
+    let terminate = (structure == Structure::Segment).then(|| {
+        quote! {
+            let terminated;
+            let unreleased = s.trim_end().strip_suffix(seps.segment).is_some_and(|s| {
+                s.chars().rev().take_while(|&c| c == seps.release).count() % 2 == 0
+            });
+            let s = if unreleased {
+                s
+            } else {
+                terminated = ::std::format!("{}{}", s, seps.segment);
+                terminated.as_str()
+            };
+        }
+    });
+    Ok(quote! {
+        impl ::core::str::FromStr for #name {
+            type Err = #util::EdifactError;
+
+            fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                let ctx = #util::ParseContext::new(#seps).strict();
+                let seps: &#util::Separators = &ctx;
+                #terminate
+                match #name::parse_with(s, &ctx) {
+                    ::core::result::Result::Ok((rest, value)) if rest.trim_matches(|c| ctx.whitespace.contains(c)).is_empty() => ::core::result::Result::Ok(value),
+                    ::core::result::Result::Ok((rest, _)) => ::core::result::Result::Err(#util::EdifactError::new("end of input", rest)
+                        .within(#label)
+                        .locate(s, seps)),
+                    ::core::result::Result::Err(::nom::Err::Error(e) | ::nom::Err::Failure(e)) => ::core::result::Result::Err(e.within(#label).locate(s, seps)),
+                    ::core::result::Result::Err(::nom::Err::Incomplete(_)) => ::core::result::Result::Err(#util::EdifactError::new("complete input", s)
+                        .within(#label)
+                        .locate(s, seps)),
+                }
+            }
+        }
+    })
+}
 
-#[proc_macro_derive(DisplayInnerSegment)]
+#[proc_macro_derive(DisplayInnerSegment, attributes(edifact))]
 pub fn display_inner(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
     let toks = generate_inner_display(&ast).unwrap_or_else(|err| err.to_compile_error());
     toks.into()
 }
 
-#[proc_macro_derive(DisplayOuterSegment)]
+#[proc_macro_derive(DisplayOuterSegment, attributes(edifact))]
 pub fn display_outer(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
     let toks = generate_outer_display(&ast).unwrap_or_else(|err| err.to_compile_error());
     toks.into()
 }
 
-#[proc_macro_derive(DisplayEdifact)]
+#[proc_macro_derive(DisplayEdifact, attributes(edifact))]
 pub fn display_edifact(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
     let toks = generate_edifact(&ast).unwrap_or_else(|err| err.to_compile_error());
     toks.into()
 }
 
-#[proc_macro_derive(DisplayEdifactSg)]
+#[proc_macro_derive(DisplayEdifactSg, attributes(edifact))]
 pub fn display_edifact_sg(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
     let toks = generate_edifact_sg(&ast).unwrap_or_else(|err| err.to_compile_error());
@@ -33,132 +141,378 @@ pub fn display_edifact_sg(input: proc_macro::TokenStream) -> proc_macro::TokenSt
 }
 
 fn generate_inner_display(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let util = util_path(ast)?;
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let output = gen_types(ast);
+    let output = gen_types(ast, &util, true, false)?;
+    let display = gen_display(
+        ast,
+        &util,
+        quote! {
+            let mut w = #util::Separated::new(f, "", seps.component);
+            #(#output)*
+            ::core::result::Result::Ok(())
+        },
+    );
+    // lets segments print the composite without a `composite` attribute on the field
     Ok(quote! {
-        impl #impl_generics ::core::fmt::Display for #name #ty_generics #where_clause {
-            fn fmt<'x>(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                let mut str: Vec<String> = vec![];
-                #(#output)*
-                let joined = str.join(":");
-                let joined = joined.trim_end_matches(":");
-                write!(f, "{}", joined)
+        #display
+        impl #impl_generics #util::DisplayValue for #name #ty_generics #where_clause {
+            fn fmt_value(&self, f: &mut ::core::fmt::Formatter<'_>, seps: &#util::Separators) -> ::core::fmt::Result {
+                self.fmt_with(f, seps)
             }
         }
     })
 }
 
 fn generate_outer_display(ast: &DeriveInput) -> syn::Result<TokenStream> {
-    let name = &ast.ident;
-    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let output = gen_types(ast);
-    let s = format_ident!("{}", name).to_string().to_uppercase();
-    Ok(quote! {
-        impl #impl_generics ::core::fmt::Display for #name #ty_generics #where_clause {
-            fn fmt<'x>(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                let mut str: Vec<String> = vec![];
-                str.push(#s.to_string());
-                #(#output)*
-                let joined = str.join("+");
-                let joined = joined.trim_end_matches("+");
-                if joined.len() > 3 {
-                    write!(f, "{}", joined)
-                }else{
-                    write!(f, "")
+    let util = util_path(ast)?;
+    let output = gen_types(ast, &util, true, true)?;
+    let s = segment_tag(ast)?;
+    Ok(gen_display(
+        ast,
+        &util,
+        quote! {
+            // the tag is only written along with the first element, a segment without any is left out
+            let mut w = #util::Separated::new(f, #s, seps.element);
+            #(#output)*
+            ::core::result::Result::Ok(())
+        },
+    ))
+}
+
+/// Reads the separators off the UNA segment `una` as it is printed, an `Option<Separators>`.
+///
+/// Only the service string advice, the first nine characters, is kept, on the stack.
+fn gen_from_una(util: &TokenStream, una: TokenStream) -> TokenStream {
+    quote! {
+        {
+            struct Advice {
+                buf: [u8; 36],
+                len: usize,
+                chars: usize,
+            }
+            impl ::core::fmt::Write for Advice {
+                fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+                    for c in s.chars().take(9 - self.chars) {
+                        self.len += c.encode_utf8(&mut self.buf[self.len..]).len();
+                        self.chars += 1;
+                    }
+                    ::core::result::Result::Ok(())
                 }
             }
+            let mut advice = Advice { buf: [0; 36], len: 0, chars: 0 };
+            ::core::fmt::Write::write_fmt(&mut advice, ::core::format_args!("{}", #una))
+                .ok()
+                .and_then(|_| ::core::str::from_utf8(&advice.buf[..advice.len]).ok())
+                .and_then(#util::Separators::from_una)
         }
-    })
+    }
 }
 
 fn generate_edifact(ast: &DeriveInput) -> syn::Result<TokenStream> {
-    let name = &ast.ident;
-    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let output = gen_types(ast);
-    Ok(quote! {
-        impl #impl_generics ::core::fmt::Display for #name #ty_generics #where_clause {
-            fn fmt<'x>(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                let mut str: Vec<String> = vec![];
+    let util = util_path(ast)?;
+    if let Some(variants) = variants(ast)? {
+        return Ok(gen_choice_display(ast, &util, &variants));
+    }
+    let output = gen_types(ast, &util, false, true)?;
+    // a message carrying a UNA segment is printed with the separators it announces
+    // the UNA segment is printed verbatim, it already ends with its own terminator
+    let (seps, una) = match find_una(ast)? {
+        Some((id, true)) => (
+            {
+                let from_una = gen_from_una(&util, quote! { una });
+                quote! {
+                    self.#id.as_ref().and_then(|una| #from_una).unwrap_or_default()
+                }
+            },
+            quote! {
+                if let ::core::option::Option::Some(una) = &self.#id {
+                    ::core::writeln!(f, "{}", una)?;
+                }
+            },
+        ),
+        Some((id, false)) => (
+            {
+                let from_una = gen_from_una(&util, quote! { &self.#id });
+                quote! { #from_una.unwrap_or_default() }
+            },
+            quote! {
+                ::core::writeln!(f, "{}", self.#id)?;
+            },
+        ),
+        None => (quote! { #util::Separators::default() }, quote! {}),
+    };
+    Ok(gen_display_with(
+        ast,
+        &util,
+        seps,
+        quote! {
+            #una
+            let written = {
+                let mut w = #util::Separated::lines(&mut *f, seps.segment);
                 #(#output)*
-                // filter empty lines
-                let str: Vec<String> = str
-                    .iter()
-                    .map(|v| v.clone())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-                let joined = str.join("'\n");
-                write!(f, "{}'", joined)
+                w.written()
+            };
+            // only the last segment written gets its terminator here
+            if written {
+                ::core::write!(f, "{}", seps.segment)
+            } else {
+                ::core::result::Result::Ok(())
             }
-        }
-    })
+        },
+    ))
 }
 
 fn generate_edifact_sg(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let util = util_path(ast)?;
+    if let Some(variants) = variants(ast)? {
+        return Ok(gen_choice_display(ast, &util, &variants));
+    }
+    let output = gen_types(ast, &util, false, true)?;
+    Ok(gen_display(
+        ast,
+        &util,
+        quote! {
+            // segments left out entirely do not leave an empty line
+            let mut w = #util::Separated::lines(f, seps.segment);
+            #(#output)*
+            ::core::result::Result::Ok(())
+        },
+    ))
+}
+
+/// Prints whichever variant of a choice enum is present.
+fn gen_choice_display(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    variants: &[EdiVariant],
+) -> TokenStream {
+    let name = &ast.ident;
+    let arms = variants.iter().map(|v| {
+        let ident = &v.ident;
+        quote! {
+            #name::#ident(value) => value.fmt_with(f, seps),
+        }
+    });
+    gen_display(
+        ast,
+        util,
+        quote! {
+            match self {
+                #(#arms)*
+            }
+        },
+    )
+}
+
+/// Wraps `body` into `fmt_with`, `Display` prints with the default separators.
+fn gen_display(ast: &DeriveInput, util: &TokenStream, body: TokenStream) -> TokenStream {
+    gen_display_with(ast, util, quote! { #util::Separators::default() }, body)
+}
+
+fn gen_display_with(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    seps: TokenStream,
+    body: TokenStream,
+) -> TokenStream {
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let output = gen_types(ast);
-    Ok(quote! {
+    quote! {
         impl #impl_generics ::core::fmt::Display for #name #ty_generics #where_clause {
-            fn fmt<'x>(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                let mut str: Vec<String> = vec![];
-                #(#output)*
-                // filter empty lines
-                let str: Vec<String> = str
-                    .iter()
-                    .map(|v| v.clone())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-                let joined = str.join("'\n");
-                write!(f, "{}", joined)
+            fn fmt<'x>(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                self.fmt_with(f, &#seps)
             }
         }
-    })
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub fn fmt_with(&self, f: &mut ::core::fmt::Formatter<'_>, seps: &#util::Separators) -> ::core::fmt::Result {
+                #body
+            }
+
+            pub fn write_to<W: ::std::io::Write>(&self, mut w: W) -> ::std::io::Result<()> {
+                ::std::io::Write::write_fmt(&mut w, ::core::format_args!("{}", self))
+            }
+        }
+    }
 }
 
-fn gen_types(ast: &DeriveInput) -> Vec<TokenStream> {
-    let x = &ast.data;
+/// Writes the fields into the `util::Separated` writer `w`.
+///
+/// Inside a segment a `Vec` is a repeating data element and its items are separated by the
+/// repetition separator, in groups and messages every item is a segment of its own.
+/// `lead` tells whether the first field is preceded by a separator, like the elements of a segment.
+fn gen_types(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    in_segment: bool,
+    lead: bool,
+) -> syn::Result<Vec<TokenStream>> {
     let mut output = vec![];
-    if let Data::Struct(s) = x {
-        let f = &s.fields;
-        for o in f {
-            let id = o.ident.clone().unwrap();
-            let t = &o.ty;
-            if let syn::Type::Path(p) = t {
-                let s = &p.path.segments;
-                let w = &s.first().unwrap().ident;
-                let ty = w.to_string();
-                match ty.as_str() {
-                    "Vec" => {
-                        let ts = quote! {
-                            if self.#id.is_empty() {
-                                str.push("".to_string());
-                            }else{
-                                self.#id.iter().for_each(|x| str.push(format!("{}",x)));
-                            }
-                        };
-                        output.push(ts);
-                    }
-                    "Option" => {
-                        let ts = quote! {
-                            str.push(self.#id.as_ref().map_or("".to_string(),|x| format!("{}",x)));
-                        };
-                        output.push(ts);
+    for (idx, field) in fields(ast)?.iter().enumerate() {
+        // the UNA segment is written by the message itself
+        if field.attrs.una {
+            continue;
+        }
+        let id = &field.ident;
+        let separate = (lead || idx > 0).then(|| quote! { w.separate(); });
+        if field.attrs.rest {
+            if !in_segment {
+                return Err(syn::Error::new_spanned(
+                    id,
+                    "rest only applies to segments and composites",
+                ));
+            }
+            // surplus values are kept escaped
+            let first = lead || idx > 0;
+            output.push(quote! {
+                for (i, x) in self.#id.iter().enumerate() {
+                    if #first || i > 0 {
+                        w.separate();
                     }
-                    _ => {
-                        let ts = quote! {
-                            str.push(format!("{}",self.#id));
-                        };
-                        output.push(ts);
+                    ::core::fmt::Write::write_fmt(&mut w, ::core::format_args!("{}", x))?;
+                }
+            });
+            continue;
+        }
+        let item = gen_value(field, util, quote! { x }, in_segment);
+        let ts = match field.shape {
+            Shape::Vec if in_segment => quote! {
+                #separate
+                {
+                    let mut w = #util::Separated::new(&mut w, "", seps.repetition);
+                    for (i, x) in self.#id.iter().enumerate() {
+                        if i > 0 {
+                            w.separate();
+                        }
+                        ::core::fmt::Write::write_fmt(&mut w, ::core::format_args!("{}", #item))?;
                     }
                 }
+            },
+            Shape::Vec => quote! {
+                for x in self.#id.iter() {
+                    w.separate();
+                    ::core::fmt::Write::write_fmt(&mut w, ::core::format_args!("{}", #item))?;
+                }
+            },
+            Shape::Option => quote! {
+                #separate
+                if let ::core::option::Option::Some(x) = &self.#id {
+                    ::core::fmt::Write::write_fmt(&mut w, ::core::format_args!("{}", #item))?;
+                }
+            },
+            Shape::Plain => {
+                let item = gen_value(field, util, quote! { &self.#id }, in_segment);
+                quote! {
+                    #separate
+                    ::core::fmt::Write::write_fmt(&mut w, ::core::format_args!("{}", #item))?;
+                }
             }
+        };
+        output.push(ts);
+    }
+    Ok(output)
+}
+
+/// Displays a single field value, `value` is a reference.
+///
+/// Data values (text and code lists) are escaped with the release character,
+/// composites, segments and groups already escape their own values.
+fn gen_value(
+    field: &EdiField,
+    util: &TokenStream,
+    value: TokenStream,
+    in_segment: bool,
+) -> TokenStream {
+    let ty = &field.ty;
+    match field.kind {
+        // segments and groups
+        _ if !in_segment => quote! {
+            #util::DisplayFn(|f: &mut ::core::fmt::Formatter<'_>| (#value).fmt_with(f, seps))
+        },
+        Kind::Text | Kind::Code => quote! {
+            #util::Escaped(#value, seps)
+        },
+        Kind::Composite => quote! {
+            #util::DisplayFn(|f: &mut ::core::fmt::Formatter<'_>| (#value).fmt_with(f, seps))
+        },
+        Kind::Value => quote! {
+            #util::DisplayFn(|f: &mut ::core::fmt::Formatter<'_>| {
+                <#ty as #util::DisplayValue>::fmt_value(#value, f, seps)
+            })
+        },
+    }
+}
+
+/// Finds the field marked with `#[edifact(una)]` and whether it is optional.
+fn find_una(ast: &DeriveInput) -> syn::Result<Option<(Ident, bool)>> {
+    Ok(fields(ast)?
+        .into_iter()
+        .find(|f| f.attrs.una)
+        .map(|f| (f.ident, f.shape == Shape::Option)))
+}
+
+/// Converts the raw value `x` in the `FromStr` derives, the expression evaluates to a `Result`.
+///
+/// `at` is the position setter of the error, `at_element(2)` or `at_component(2)`.
+fn gen_from_str_value(field: &EdiField, util: &TokenStream, at: &TokenStream) -> TokenStream {
+    let ty = &field.ty;
+    let label = field.label();
+    match field.kind {
+        Kind::Text => quote! {
+            ::core::result::Result::<_, #util::EdifactError>::Ok(#util::unescape(x, &seps))
+        },
+        Kind::Code => {
+            let expected = format!("code of {label}");
+            quote! {
+                <#ty as ::core::str::FromStr>::from_str(&#util::unescape(x, &seps))
+                    .map_err(|_| #util::EdifactError::new(#expected, x).within(#label).#at)
+            }
+        }
+        // composites know where they failed
+        Kind::Composite => quote! {
+            <#ty as ::core::str::FromStr>::from_str(x)
+                .map_err(|e: #util::EdifactError| e.within(#label).#at)
+        },
+        Kind::Value => quote! {
+            <#ty as #util::ParseValue>::parse_value(x, &ctx)
+                .map_err(|e| e.within(#label).#at)
+        },
+    }
+}
+
+/// Rejects an absent or empty mandatory value in the `FromStr` derives, like the parsers do.
+///
+/// The error points behind the value before it.
+fn gen_missing(field: &EdiField, util: &TokenStream, idx: usize, at: &TokenStream) -> TokenStream {
+    let label = field.label();
+    quote! {
+        {
+            let at = parts.get(#idx).or(parts.last()).map_or(&s[..0], |v| &v[v.len()..]);
+            return ::core::result::Result::Err(#util::EdifactError::new("mandatory value", at).within(#label).#at);
         }
     }
-    output
 }
 
-#[proc_macro_derive(ParseInnerSegment)]
+/// Rejects more repetitions than allowed by `#[edifact(max_repeat = ..)]` in the `FromStr` derives.
+fn gen_max_repeat(
+    field: &EdiField,
+    util: &TokenStream,
+    value: TokenStream,
+    at: &TokenStream,
+) -> Option<TokenStream> {
+    let label = field.label();
+    field.attrs.max_repeat.map(|max| {
+        let expected = format!("at most {max} repetitions");
+        quote! {
+            if items.len() > #max {
+                return ::core::result::Result::Err(#util::EdifactError::new(#expected, #value).within(#label).#at);
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(ParseInnerSegment, attributes(edifact))]
 pub fn parse_inner(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
     let toks = generate_inner_parse(&ast).unwrap_or_else(|err| err.to_compile_error());
@@ -166,65 +520,88 @@ pub fn parse_inner(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 }
 
 fn generate_inner_parse(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let util = util_path(ast)?;
+    reject_span(ast)?;
+    reject_unknown(ast)?;
+    reject_borrowed(ast, "ParseElement")?;
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let output = gen_inner_props(ast);
-    let prop_count = output.len();
+    let output = gen_inner_props(ast, &util)?;
+    // a `rest` field takes the surplus components, empty trailing ones are accepted
+    let surplus = find_surplus(ast, &util, true, quote! { parts })?.map(|surplus| {
+        quote! {
+            if let ::core::option::Option::Some(e) = #surplus {
+                return ::core::result::Result::Err(e);
+            }
+        }
+    });
     Ok(quote! {
         impl #impl_generics ::core::str::FromStr for #name #ty_generics #where_clause {
-            type Err = ParseError;
+            type Err = #util::EdifactError;
 
-            fn from_str(s: &str) -> Result<Self, Self::Err> {
-                let parts: Vec<&str> = s.split(':').collect();
-                if parts.len() > #prop_count {
-                    Err(ParseError {
-                        msg: "too many segments".to_string(),
-                    })
-                } else {
-                    Ok(#name {
+            fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                let ctx = #util::ParseContext::new(#util::Separators::default()).strict();
+                let seps: &#util::Separators = &ctx;
+                let parse = || {
+                    let parts: ::std::vec::Vec<&str> = #util::split_released(s, seps.component, &seps);
+                    #surplus
+                    ::core::result::Result::Ok(#name {
                         #(#output)*
                     })
-                }
+                };
+                parse().map_err(|e| e.locate(s, &seps))
             }
         }
     })
 }
 
-fn gen_inner_props(ast: &DeriveInput) -> Vec<TokenStream> {
-    let x = &ast.data;
+fn gen_inner_props(ast: &DeriveInput, util: &TokenStream) -> syn::Result<Vec<TokenStream>> {
     let mut output = vec![];
-    let mut idx: usize = 0;
-    if let Data::Struct(s) = x {
-        let f = &s.fields;
-        for o in f {
-            let id = o.ident.clone().unwrap();
-            let t = &o.ty;
-            if let syn::Type::Path(p) = t {
-                let s = &p.path.segments;
-                let w = &s.first().unwrap().ident;
-                let ty = w.to_string();
-                match ty.as_str() {
-                    "Option" => {
-                        let chunk = quote! {
-                            #id: parts.get(#idx).map(|x| x.to_string()),
-                        };
-                        output.push(chunk);
-                    }
-                    _ => {
-                        let chunk = quote! {
-                            #id: parts.get(#idx).map(|x| x.to_string()).unwrap_or_default(),
-                        };
-                        output.push(chunk);
-                    }
+    for (idx, field) in fields(ast)?.iter().enumerate() {
+        let id = &field.ident;
+        let pos = idx + 1;
+        let at = quote! { at_component(#pos) };
+        if field.attrs.rest {
+            output.push(quote! {
+                #id: parts.iter().skip(#idx).map(|&x| ::core::convert::From::from(x)).collect(),
+            });
+            continue;
+        }
+        let item = gen_from_str_value(field, util, &at);
+        let chunk = match field.shape {
+            Shape::Vec => {
+                let max = gen_max_repeat(field, util, quote! { parts[#idx] }, &at);
+                quote! {
+                    #id: {
+                        let items = parts
+                            .get(#idx)
+                            .filter(|&f| !f.is_empty())
+                            .map(|x| #util::split_released(x, seps.repetition, &seps))
+                            .unwrap_or_default();
+                        #max
+                        items.into_iter().map(|x| #item).collect::<::core::result::Result<::std::vec::Vec<_>, _>>()?
+                    },
                 }
             }
-            idx += 1;
-        }
+            Shape::Option => quote! {
+                #id: parts.get(#idx).filter(|&f| !f.is_empty()).map(|&x| #item).transpose()?,
+            },
+            Shape::Plain => {
+                let missing = gen_missing(field, util, idx, &at);
+                quote! {
+                    #id: match parts.get(#idx).filter(|&f| !f.is_empty()) {
+                        ::core::option::Option::Some(&x) => #item?,
+                        ::core::option::Option::None => #missing,
+                    },
+                }
+            }
+        };
+        output.push(chunk);
     }
-    output
+    Ok(output)
 }
 
-#[proc_macro_derive(ParseElement)]
+#[proc_macro_derive(ParseElement, attributes(edifact))]
 pub fn parse_element(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let output = generate_element_parser(&input).unwrap_or_else(|err| err.to_compile_error());
@@ -234,35 +611,61 @@ pub fn parse_element(input: proc_macro::TokenStream) -> proc_macro::TokenStream
 }
 
 fn generate_element_parser(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let util = util_path(ast)?;
+    reject_span(ast)?;
+    reject_unknown(ast)?;
     let name = &ast.ident;
-    let tok = parse_all(ast);
+    let tok = parse_all(ast, &util, true)?;
+    let idents = fields(ast)?.into_iter().map(|f| f.ident);
+    let surplus = gen_surplus(ast, &util, true)?;
     let s = format_ident!("{}", name).to_string().to_uppercase();
-    let res = quote! {
-        impl<'a> crate::util::Parser<&'a str, #name, nom::error::Error<&'a str>> for #name {
-            fn parse(input: &'a str) -> ::nom::IResult<&'a str, #name> {
-                #[cfg(feature = "logging")]
-                log::debug!("Parser is inside {}", #s);
-                let (_, vars) = crate::util::parse_colon_section(input)?;
-                #[cfg(feature = "logging")]
-                log::debug!("Variables created {vars:?}");
-                let output = #name {
-                    #(#tok)*
-                };
-                Ok(("", output))
+    let unwrap = gen_unwrap_text(ast, &util, None)?;
+    let res = gen_parser(
+        ast,
+        &util,
+        &s,
+        quote! { #util::Separators::default() },
+        quote! {
+            #unwrap
+            #[cfg(feature = "logging")]
+            ::log::debug!("Parser is inside {}", #s);
+            let vars = #util::split_released(input, ctx.component, ctx);
+            #[cfg(feature = "logging")]
+            ::log::debug!("Variables created {vars:?}");
+            #surplus
+            #(#tok)*
+            let output = #name {
+                #(#idents),*
+            };
+            ::core::result::Result::Ok(("", output))
+        },
+    )?;
+    let into_owned = gen_into_owned(ast)?;
+    let Input { lt, ty, .. } = input(ast)?;
+    // lets segments parse the composite without a `composite` attribute on the field
+    Ok(quote! {
+        #res
+        #into_owned
+        impl<#lt> #util::ParseValue<#lt> for #ty {
+            fn parse_value(raw: &#lt str, ctx: &#util::ParseContext) -> ::core::result::Result<Self, #util::EdifactError> {
+                match #name::parse_with(raw, ctx) {
+                    ::core::result::Result::Ok((_, value)) => ::core::result::Result::Ok(value),
+                    ::core::result::Result::Err(::nom::Err::Error(e) | ::nom::Err::Failure(e)) => ::core::result::Result::Err(e),
+                    ::core::result::Result::Err(::nom::Err::Incomplete(_)) => ::core::result::Result::Err(#util::EdifactError::new("complete input", raw)),
+                }
             }
         }
-    };
-    Ok(res)
+    })
 }
 
-#[proc_macro_derive(ParseSg)]
+#[proc_macro_derive(ParseSg, attributes(edifact))]
 pub fn parse_sg(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let output = generate_sg_parser(&input, true).unwrap_or_else(|err| err.to_compile_error());
     proc_macro::TokenStream::from(output)
 }
 
-#[proc_macro_derive(ParseMsg)]
+#[proc_macro_derive(ParseMsg, attributes(edifact))]
 pub fn parse_msg(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let output = generate_sg_parser(&input, false).unwrap_or_else(|err| err.to_compile_error());
@@ -271,106 +674,790 @@ pub fn parse_msg(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     proc_macro::TokenStream::from(output)
 }
 
+/// A segment with another qualifier is an `Error`, so the next field gets a chance.
+fn field_qualifier(attrs: &FieldAttrs) -> TokenStream {
+    match &attrs.qualifier {
+        Some(q) => quote! { ::core::option::Option::Some(#q) },
+        None => quote! { ::core::option::Option::None },
+    }
+}
+
+/// The qualifier a choice hands to a variant, one given to the choice itself comes first.
+fn variant_qualifier(variant: &EdiVariant) -> TokenStream {
+    let q = field_qualifier(&variant.attrs);
+    quote! { qualifier.or(#q) }
+}
+
 fn generate_sg_parser(ast: &DeriveInput, is_sg: bool) -> syn::Result<TokenStream> {
+    let util = util_path(ast)?;
     let name = &ast.ident;
+    if let Some(variants) = variants(ast)? {
+        reject_unknown(ast)?;
+        let parser = gen_choice_parser(ast, &util, &variants, is_sg)?;
+        let into_owned = gen_into_owned(ast)?;
+        return Ok(quote! {
+            #parser
+            #into_owned
+        });
+    }
+    if is_sg {
+        reject_unknown(ast)?;
+    }
+    let fields = fields(ast)?;
+    let lt = input(ast)?.lt;
+    // the group is committed once its trigger matched, later violations are a `Failure`
+    let trigger = match fields.iter().filter(|f| f.attrs.trigger).count() {
+        0 if is_sg => Some(0),
+        0 => None,
+        1 => fields.iter().position(|f| f.attrs.trigger),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "only one trigger segment per group is allowed",
+            ))
+        }
+    };
     let mut lefties = vec![];
     let mut attries = vec![];
-    if let Data::Struct(left_vec) = &ast.data {
-        if let Fields::Named(f) = &left_vec.fields {
-            for (idx, ff) in (&f.named).into_iter().enumerate() {
-                let left = match &ff.ident {
-                    Some(l) => l.clone(),
-                    None => Ident::new("", Span::call_site()),
+    // the segments the group can start with, up to its first mandatory field
+    let mut starts = vec![];
+    let mut required = false;
+    // a qualifier handed to the group or message goes to the segment it starts with
+    let qualified = trigger.or_else(|| fields.iter().position(|f| !f.attrs.una));
+    let qualifiers: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let q = field_qualifier(&field.attrs);
+            if qualified == Some(idx) {
+                quote! { qualifier.or(#q) }
+            } else {
+                q
+            }
+        })
+        .collect();
+    // looks at the next segment tag and qualifier before running the parser
+    let peeks: Vec<_> = fields
+        .iter()
+        .zip(&qualifiers)
+        .map(|(field, q)| {
+            let ty = &field.ty;
+            if field.attrs.una {
+                quote! { |t: &[#util::RawSegment<'_>]| t.first().is_some_and(|t| t.tag == "UNA") }
+            } else {
+                quote! { |t: &[#util::RawSegment<'_>]| <#ty>::can_start(t, ctx, #q) }
+            }
+        })
+        .collect();
+    // the tags of all segments inside, however deep
+    let knows: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            let ty = &field.ty;
+            if field.attrs.una {
+                quote! { tag == "UNA" }
+            } else {
+                quote! { <#ty>::knows_tag(tag) }
+            }
+        })
+        .collect();
+    for (idx, field) in fields.iter().enumerate() {
+        let left = &field.ident;
+        let ty = &field.ty;
+        let label = left.to_string().to_uppercase();
+        if field.attrs.rest {
+            return Err(syn::Error::new_spanned(
+                left,
+                "rest only applies to segments and composites",
+            ));
+        }
+        if (field.attrs.min.is_some() || field.attrs.max.is_some()) && field.shape != Shape::Vec {
+            return Err(syn::Error::new_spanned(
+                left,
+                "min and max only apply to repeated fields",
+            ));
+        }
+        let is_trigger = trigger == Some(idx);
+        let committed = trigger.is_none_or(|t| idx > t);
+        let violation = if committed {
+            quote! { ::nom::Err::Failure }
+        } else {
+            quote! { ::nom::Err::Error }
+        };
+        let q = &qualifiers[idx];
+        // the UNA segment only knows the default parser
+        let ti = if field.attrs.una {
+            quote! {
+                |t: &'t [#util::RawSegment<#lt>]| match t.split_first() {
+                    ::core::option::Option::Some((token, rest)) if token.tag == "UNA" => {
+                        <#ty as #util::Parser<&str, #ty, #util::EdifactError>>::parse(token.raw)
+                            .map(|(_, v)| (rest, v))
+                    }
+                    _ => ::core::result::Result::Err(::nom::Err::Error(#util::EdifactError::new("UNA", t.first().map_or("", |t| t.tag)))),
+                }
+            }
+        } else {
+            // diagnostics reported further down get the label of this field
+            quote! {
+                |t| {
+                    if ctx.recover {
+                        let mark = ctx.mark();
+                        let r = <#ty>::parse_tokens(t, ctx, #q);
+                        ctx.within_since(mark, #label);
+                        r
+                    } else {
+                        <#ty>::parse_tokens(t, ctx, #q)
+                    }
+                }
+            }
+        };
+        let call = quote! { (#ti)(outer_rest) };
+        let peek = &peeks[idx];
+        // a broken segment is skipped with the ones following it up to a segment this or a
+        // later field can start, segments none of them knows are left to the parent
+        let (tail_peeks, tail_knows) = (&peeks[idx..], &knows[idx..]);
+        let resync = |from: TokenStream| {
+            quote! {
+                {
+                    let mut t = &#from[1..];
+                    while t.first().is_some_and(|s| { let tag = s.tag; #(#tail_knows)||* })
+                        && !(#((#tail_peeks)(t))||*)
+                    {
+                        t = &t[1..];
+                    }
+                    t
+                }
+            }
+        };
+        let skip_rest = resync(quote! { rest });
+        let skip_outer = resync(quote! { outer_rest });
+        // a segment out of place is passed over up to the next one the field can start, unless
+        // an earlier field could take it
+        let head_peeks = &peeks[..idx];
+        let search = quote! {
+            (1..outer_rest.len())
+                .map(|i| &outer_rest[i..])
+                .take_while(|t| !(false #(|| (#head_peeks)(t))*))
+                .find(|t| (#peek)(t))
+        };
+        // the trigger occurs at least once
+        let min = field.attrs.min.unwrap_or(0).max(usize::from(is_trigger));
+        if !required {
+            starts.push(quote! { (#peek)(tokens) });
+            required = is_trigger || field.shape == Shape::Plain || min > 0;
+        }
+        let attr = match field.shape {
+            Shape::Vec => {
+                let limit = field.attrs.max.map(|max| quote! { #left.len() < #max && });
+                // once committed, a recovering parse reports a violation and keeps what it has
+                let recovering = if committed {
+                    quote! { ctx.recover }
+                } else {
+                    quote! { false }
                 };
-
-                // right side can be any of String, Struct, Enum, Option<..>, Vec<..>
-
-                let all = if let Type::Path(tyty) = &ff.ty {
-                    let inside = &tyty.path.segments[0];
-                    let inside_str = inside.ident.to_string();
-                    // First occurence, can be Option, Vec or Type
-                    match inside_str.as_str() {
-                        "Vec" | "Option" => {
-                            if let PathArguments::AngleBracketed(inside_optvec) = &inside.arguments
-                            {
-                                if let GenericArgument::Type(Type::Path(t)) = &inside_optvec.args[0]
-                                {
-                                    let ti = &t.path.segments[0].ident;
-                                    (
-                                        quote! {
-                                            #left
-                                        },
-                                        if inside_str == "Vec" {
-                                            // if we are in msg_type stage, or if vec is the first element of struct
-                                            // many0 will come back positive and the surroung element will not collaps
-                                            if !is_sg || idx != 0 {
-                                                quote! {
-                                                // let (outer_rest, dtm) = many0(DTM::parse)(outer_rest)?;
-                                                    let (outer_rest, #left) = nom::multi::many0(#ti::parse)(outer_rest)?;
-                                                }
-                                            } else {
-                                                quote! {
-                                                    let (outer_rest, #left) = nom::multi::many1(#ti::parse)(outer_rest)?;
-                                                }
-                                            }
-                                        } else {
-                                            quote! {
-                                                let (outer_rest, #left) = nom::combinator::opt(#ti::parse)(outer_rest)?;
-                                            }
-                                        },
-                                    )
-                                } else {
-                                    (quote! {}, quote! {})
+                let min_check = (min > 0).then(|| {
+                    let expected = format!("at least {min} occurrences");
+                    quote! {
+                        if #left.len() < #min {
+                            let tag = outer_rest.first().map_or("", |t| t.tag);
+                            let e = #util::EdifactError::new(#expected, tag).within(#label);
+                            if !#recovering {
+                                return ::core::result::Result::Err(#violation(e));
+                            }
+                            ctx.report(e);
+                        }
+                    }
+                });
+                // the surplus occurrences are left out
+                let max_check = field.attrs.max.map(|max| {
+                    let expected = format!("at most {max} occurrences");
+                    quote! {
+                        let outer_rest = match (#peek)(outer_rest).then(|| (#ti)(outer_rest)) {
+                            ::core::option::Option::Some(::core::result::Result::Ok((mut rest, _))) => {
+                                let tag = outer_rest.first().map_or("", |t| t.tag);
+                                let e = #util::EdifactError::new(#expected, tag).within(#label);
+                                if !#recovering {
+                                    return ::core::result::Result::Err(#violation(e));
+                                }
+                                ctx.report(e);
+                                while (#peek)(rest) {
+                                    match (#ti)(rest) {
+                                        ::core::result::Result::Ok((r, _)) if r.len() < rest.len() => rest = r,
+                                        _ => break,
+                                    }
                                 }
-                            } else {
-                                (quote! {}, quote! {})
+                                rest
                             }
+                            _ => outer_rest,
+                        };
+                    }
+                });
+                quote! {
+                    let mut rest = outer_rest;
+                    let mut #left = ::std::vec::Vec::new();
+                    while #limit (#peek)(rest) {
+                        match (#ti)(rest) {
+                            ::core::result::Result::Ok((r, item)) if r.len() < rest.len() => {
+                                rest = r;
+                                #left.push(item);
+                            }
+                            ::core::result::Result::Ok(_) | ::core::result::Result::Err(::nom::Err::Error(_)) => break,
+                            // a broken occurrence is reported and skipped
+                            ::core::result::Result::Err(::nom::Err::Failure(e)) if ctx.recover => {
+                                ctx.report(e.within(#label));
+                                rest = #skip_rest;
+                            }
+                            ::core::result::Result::Err(e) => return ::core::result::Result::Err(e.map(|e| e.within(#label))),
                         }
-                        _ => {
-                            let i = inside.ident.clone();
-                            (
-                                quote! {
-                                    #left
-                                },
-                                quote! {
-                                    // let (outer_rest, loc) = LOC::parse(input)?;
-                                    let (outer_rest, #left) = #i::parse(outer_rest)?;
-                                },
-                            )
+                    }
+                    let outer_rest = rest;
+                    #min_check
+                    #max_check
+                }
+            }
+            // a trigger has to be there, otherwise the whole group is absent
+            Shape::Option if is_trigger => quote! {
+                let (outer_rest, #left) = #call
+                    .map(|(rest, v)| (rest, ::core::option::Option::Some(v)))
+                    .map_err(|e| e.map(|e| e.within(#label)))?;
+            },
+            Shape::Option => quote! {
+                let (outer_rest, #left) = if (#peek)(outer_rest) {
+                    match (#ti)(outer_rest) {
+                        ::core::result::Result::Ok((rest, v)) => (rest, ::core::option::Option::Some(v)),
+                        ::core::result::Result::Err(::nom::Err::Error(_)) => (outer_rest, ::core::option::Option::None),
+                        ::core::result::Result::Err(::nom::Err::Failure(e)) if ctx.recover => {
+                            ctx.report(e.within(#label));
+                            (#skip_outer, ::core::option::Option::None)
                         }
+                        ::core::result::Result::Err(e) => return ::core::result::Result::Err(e.map(|e| e.within(#label))),
                     }
                 } else {
-                    (quote! {}, quote! {})
+                    (outer_rest, ::core::option::Option::None)
                 };
-                lefties.push(all.0);
-                attries.push(all.1);
-            }
+            },
+            // when recovering, broken segments are skipped until the field or a later one is found
+            Shape::Plain if committed => quote! {
+                let (outer_rest, #left) = {
+                    let mut outer_rest = outer_rest;
+                    loop {
+                        match #call {
+                            ::core::result::Result::Ok(v) => break v,
+                            ::core::result::Result::Err(::nom::Err::Failure(e)) if ctx.recover && !outer_rest.is_empty() => {
+                                ctx.report(e.within(#label));
+                                outer_rest = #skip_outer;
+                            }
+                            ::core::result::Result::Err(::nom::Err::Error(e)) => match ctx.recover.then(|| #search).flatten() {
+                                ::core::option::Option::Some(t) => {
+                                    ctx.report(e.within(#label));
+                                    outer_rest = t;
+                                }
+                                _ => return ::core::result::Result::Err(::nom::Err::Failure(e.within(#label))),
+                            },
+                            ::core::result::Result::Err(e) => return ::core::result::Result::Err(e.map(|e| e.within(#label))),
+                        }
+                    }
+                };
+            },
+            Shape::Plain => quote! {
+                // let (outer_rest, loc) = LOC::parse(input)?;
+                let (outer_rest, #left) = #call
+                    .map_err(|e| e.map(|e| e.within(#label)))?;
+            },
+        };
+        lefties.push(quote! { #left });
+        attries.push(attr);
+    }
+    // the span reaches from the first to the last segment taken, reordered ones are not in input order
+    let span = span_field(ast)?.map(|id| {
+        lefties.push(quote! { #id });
+        quote! {
+            let consumed = &tokens[..tokens.len() - outer_rest.len()];
+            let first = consumed.iter().min_by_key(|t| t.span.start);
+            let end = consumed.iter().map(|t| t.span.end).max();
+            let #id = match (first, end) {
+                (::core::option::Option::Some(first), ::core::option::Option::Some(end)) => {
+                    #util::Span::new(first.span.start, end, first.span.segment)
+                }
+                _ => ::core::default::Default::default(),
+            };
         }
-    };
+    });
     let s = format_ident!("{}", name).to_string().to_uppercase();
+    // a message picks up the separators from a leading UNA segment
+    let seps = if is_sg {
+        quote! { #util::Separators::default() }
+    } else {
+        quote! { #util::Separators::from_una(input).unwrap_or_default() }
+    };
+    // a group without mandatory fields can start anywhere
+    let can_start = if required {
+        quote! { #(#starts)||* }
+    } else {
+        quote! { true }
+    };
+    // the unknown segments are filled in after parsing the known ones
+    if let Some(id) = unknown_field(ast)? {
+        lefties.push(quote! { #id: ::std::vec::Vec::new() });
+    }
+    let singles = fields.iter().map(|f| f.shape != Shape::Vec);
+    let labels = fields.iter().map(|f| f.ident.to_string().to_uppercase());
+    let (body, ordered) = gen_reorder(
+        ast,
+        &util,
+        &s,
+        quote! {
+            #[cfg(feature = "logging")]
+            ::log::debug!("Parser is inside {}", #s);
+            let outer_rest = tokens;
+            #(#attries)*
+            #span
+            ::core::result::Result::Ok((outer_rest, #name { #(#lefties),* }))
+        },
+        &knows,
+        quote! { #(#singles),* },
+        quote! { #(#labels),* },
+    )?;
+    let (body, known) = gen_unknown(ast, &util, body)?;
+    let parser = gen_token_parser(
+        ast,
+        &util,
+        &s,
+        seps,
+        body,
+        can_start,
+        quote! { false #(|| #knows)* },
+    )?;
+    let into_owned = gen_into_owned(ast)?;
+    let recovering = (!is_sg)
+        .then(|| gen_recovering(ast, &util, &s))
+        .transpose()?;
     let res = quote! {
-        // impl<'a> Parser<&'a str, IftminSg1, nom::error::Error<&'a str>> for IftminSg1 {
-        //     fn parse(input: &'a str) -> IResult<&'a str, IftminSg1> {
-        //         let (outer_rest, loc) = LOC::parse(input)?;
-        //         let (outer_rest, dtm) = many0(DTM::parse)(outer_rest)?;
-        //         Ok((outer_rest, IftminSg1 { loc, dtm }))
-        //     }
-        // }
-        impl<'a> crate::util::Parser<&'a str, #name, nom::error::Error<&'a str>> for #name {
-            fn parse(input: &'a str) -> ::nom::IResult<&'a str, #name> {
-                #[cfg(feature = "logging")]
-                log::debug!("Parser is inside {}", #s);
-                let outer_rest = input;
-                #(#attries)*
-                Ok((outer_rest, #name { #(#lefties),* }))
-            }
-        }
+        #parser
+        #ordered
+        #known
+        #into_owned
+        #recovering
     };
     #[cfg(feature = "debug")]
     println!("{res}");
     Ok(res)
 }
 
+/// Lets a group or message take its segments in any order if the `ParseContext` asks for it.
+///
+/// `body` becomes `parse_ordered`. Reordered are the segments from the start whose tag exactly one field
+/// knows, up to a segment of another group, one several fields know or the second occurrence of a field
+/// that does not repeat. They are sorted by field and put in front of the segments after them, which
+/// stay in order. The sorted ones have to be taken completely, otherwise the input is parsed as it is.
+/// Every segment moved forward is reported as a warning. `knows` tells per field whether it knows `tag`,
+/// `singles` whether it does not repeat and `labels` name it.
+fn gen_reorder(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    label: &str,
+    body: TokenStream,
+    knows: &[TokenStream],
+    singles: TokenStream,
+    labels: TokenStream,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let name = &ast.ident;
+    let n = knows.len();
+    let Input {
+        lt,
+        impl_generics,
+        fn_lts,
+        ty,
+        ..
+    } = input(ast)?;
+    let dispatch = quote! {
+        if ctx.reorder {
+            let single: [bool; #n] = [#singles];
+            let labels: [&str; #n] = [#labels];
+            let mut seen = [false; #n];
+            let mut last = ::core::option::Option::None;
+            // the field of each segment taken into account
+            let mut owners = ::std::vec::Vec::new();
+            for t in tokens {
+                let tag = t.tag;
+                let known: [bool; #n] = [#(#knows),*];
+                let mut found = (0..#n).filter(|&i| known[i]);
+                // a segment known to several fields could be taken by either
+                let owner = match (found.next(), found.next()) {
+                    (::core::option::Option::Some(owner), ::core::option::Option::None) => owner,
+                    _ => break,
+                };
+                if last != ::core::option::Option::Some(owner) {
+                    if seen[owner] && single[owner] {
+                        break;
+                    }
+                    seen[owner] = true;
+                    last = ::core::option::Option::Some(owner);
+                }
+                owners.push(owner);
+            }
+            if owners.windows(2).any(|w| w[0] > w[1]) {
+                let mut order: ::std::vec::Vec<usize> = (0..owners.len()).collect();
+                order.sort_by_key(|&i| owners[i]);
+                let after = &tokens[owners.len()..];
+                let reordered: ::std::vec::Vec<_> = order
+                    .iter()
+                    .map(|&i| &tokens[i])
+                    .chain(after)
+                    .cloned()
+                    .collect();
+                if let ::core::result::Result::Ok((rest, value)) = #name::parse_ordered(&reordered, ctx, qualifier) {
+                    if rest.len() <= after.len() {
+                        let mut furthest = 0;
+                        for (t, &owner) in tokens.iter().zip(&owners) {
+                            if owner < furthest {
+                                let expected = ::std::format!("{} before {}", labels[owner], labels[furthest]);
+                                ctx.warn(#util::EdifactError::new(&expected, t.tag).within(#label));
+                            }
+                            furthest = furthest.max(owner);
+                        }
+                        return ::core::result::Result::Ok((&tokens[tokens.len() - rest.len()..], value));
+                    }
+                }
+            }
+        }
+        #name::parse_ordered(tokens, ctx, qualifier)
+    };
+    let ordered = quote! {
+        impl #impl_generics #ty {
+            fn parse_ordered<#fn_lts 't>(
+                tokens: &'t [#util::RawSegment<#lt>],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> ::nom::IResult<&'t [#util::RawSegment<#lt>], #ty, #util::EdifactError> {
+                #body
+            }
+        }
+    };
+    Ok((dispatch, ordered))
+}
+
+/// Applies the policy of a message for segments it does not model around the parser `body`.
+///
+/// Without a policy `body` is the parser. Otherwise it becomes `parse_known`, running on the segments
+/// whose tag `knows_tag` accepts. Unknown segments up to the last segment taken belong to the message,
+/// they are rejected, reported or kept in the `unknown` field. Input without them is parsed as it is.
+fn gen_unknown(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    body: TokenStream,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let name = &ast.ident;
+    let field = unknown_field(ast)?;
+    let policy = ContainerAttrs::from_ast(ast)?.unknown;
+    let handle = match (policy, &field) {
+        (None, None) => return Ok((body, quote! {})),
+        (Some(_), Some(id)) => {
+            return Err(syn::Error::new_spanned(
+                id,
+                "an unknown field and an unknown policy exclude each other",
+            ))
+        }
+        (Some(Unknown::Reject), None) => quote! {
+            if let ::core::option::Option::Some(t) = unknown.next() {
+                return ::core::result::Result::Err(::nom::Err::Failure(#util::EdifactError::new("known segment", t.tag)));
+            }
+        },
+        (Some(Unknown::Skip), None) => quote! {
+            for t in unknown {
+                ctx.report(#util::EdifactError::new("known segment", t.tag));
+            }
+        },
+        (None, Some(id)) => quote! {
+            value.#id = unknown.cloned().collect();
+        },
+    };
+    let mut_value = field.is_some().then(|| quote! { mut });
+    let mut_unknown = (policy == Some(Unknown::Reject)).then(|| quote! { mut });
+    let Input {
+        lt,
+        impl_generics,
+        fn_lts,
+        ty,
+        ..
+    } = input(ast)?;
+    let dispatch = quote! {
+        if !ctx.recover {
+            if let ::core::result::Result::Ok((rest, value)) = #name::parse_known(tokens, ctx, qualifier) {
+                if rest.first().is_none_or(|t| #name::knows_tag(t.tag)) {
+                    return ::core::result::Result::Ok((rest, value));
+                }
+            }
+        }
+        let (known, at): (::std::vec::Vec<_>, ::std::vec::Vec<usize>) = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| #name::knows_tag(t.tag))
+            .map(|(i, t)| (t.clone(), i))
+            .unzip();
+        let (rest, #mut_value value) = #name::parse_known(&known, ctx, qualifier)?;
+        // unknown segments after the last one taken are left over
+        let end = match known.len() - rest.len() {
+            0 => 0,
+            n => at[n - 1] + 1,
+        };
+        let #mut_unknown unknown = tokens[..end].iter().filter(|t| !#name::knows_tag(t.tag));
+        #handle
+        ::core::result::Result::Ok((&tokens[end..], value))
+    };
+    let known = quote! {
+        impl #impl_generics #ty {
+            fn parse_known<#fn_lts 't>(
+                tokens: &'t [#util::RawSegment<#lt>],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> ::nom::IResult<&'t [#util::RawSegment<#lt>], #ty, #util::EdifactError> {
+                #body
+            }
+        }
+    };
+    Ok((dispatch, known))
+}
+
+/// Groups, segments and composites leave the segments they do not model to the message.
+fn reject_unknown(ast: &DeriveInput) -> syn::Result<()> {
+    let field = match variants(ast)? {
+        Some(_) => None,
+        None => unknown_field(ast)?,
+    };
+    if let Some(id) = field {
+        return Err(syn::Error::new_spanned(
+            id,
+            "unknown only applies to messages",
+        ));
+    }
+    if ContainerAttrs::from_ast(ast)?.unknown.is_some() {
+        return Err(syn::Error::new_spanned(
+            &ast.ident,
+            "unknown only applies to messages",
+        ));
+    }
+    Ok(())
+}
+
+/// Implements `parse_recovering` of a message, returning what could be parsed along with the problems.
+///
+/// Broken repeated and conditional segments or groups are reported and skipped a segment at a time,
+/// a mandatory one is searched for further on, passing over the segments out of place. Only a message missing a mandatory part comes back as `None`.
+fn gen_recovering(ast: &DeriveInput, util: &TokenStream, label: &str) -> syn::Result<TokenStream> {
+    let Input {
+        lt,
+        impl_generics,
+        fn_lts,
+        ty,
+        ..
+    } = input(ast)?;
+    Ok(quote! {
+        impl #impl_generics #ty {
+            pub fn parse_recovering<#fn_lts>(
+                input: &#lt str,
+            ) -> (::core::option::Option<#ty>, ::std::vec::Vec<#util::EdifactError>) {
+                let ctx = #util::ParseContext::new(#util::Separators::from_una(input).unwrap_or_default()).recover();
+                let result = <#ty>::parse_with(input, &ctx);
+                let mut diagnostics = ctx.take_diagnostics();
+                let value = match result {
+                    ::core::result::Result::Ok((rest, value)) => {
+                        if !rest.trim_matches(|c| ctx.whitespace.contains(c)).is_empty() {
+                            diagnostics.push(#util::EdifactError::new("end of input", rest));
+                        }
+                        ::core::option::Option::Some(value)
+                    }
+                    ::core::result::Result::Err(::nom::Err::Error(e) | ::nom::Err::Failure(e)) => {
+                        diagnostics.push(e);
+                        ::core::option::Option::None
+                    }
+                    ::core::result::Result::Err(::nom::Err::Incomplete(_)) => {
+                        diagnostics.push(#util::EdifactError::new("complete input", input));
+                        ::core::option::Option::None
+                    }
+                };
+                let diagnostics = diagnostics
+                    .into_iter()
+                    .map(|e| e.within(#label).locate(input, &ctx))
+                    .collect();
+                (value, diagnostics)
+            }
+        }
+    })
+}
+
+/// Parses one of several segments or groups at the same position.
+///
+/// The variants are tried in order, an `Error` moves on to the next one,
+/// a `Failure` inside a matching variant is returned right away.
+fn gen_choice_parser(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    variants: &[EdiVariant],
+    is_sg: bool,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let s = name.to_string().to_uppercase();
+    let expected = format!(
+        "one of {}",
+        variants
+            .iter()
+            .map(|v| v.ident.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let tries = variants.iter().map(|v| {
+        let ident = &v.ident;
+        let ty = &v.ty;
+        let label = ident.to_string().to_uppercase();
+        let q = variant_qualifier(v);
+        quote! {
+            if <#ty>::can_start(tokens, ctx, #q) {
+                match <#ty>::parse_tokens(tokens, ctx, #q) {
+                    ::core::result::Result::Ok((rest, value)) => return ::core::result::Result::Ok((rest, #name::#ident(value))),
+                    ::core::result::Result::Err(::nom::Err::Error(_)) => {}
+                    ::core::result::Result::Err(e) => return ::core::result::Result::Err(e.map(|e| e.within(#label))),
+                }
+            }
+        }
+    });
+    let seps = if is_sg {
+        quote! { #util::Separators::default() }
+    } else {
+        quote! { #util::Separators::from_una(input).unwrap_or_default() }
+    };
+    let types = variants.iter().map(|v| &v.ty);
+    let starts = variants.iter().map(|v| {
+        let ty = &v.ty;
+        let q = variant_qualifier(v);
+        quote! { <#ty>::can_start(tokens, ctx, #q) }
+    });
+    gen_token_parser(
+        ast,
+        util,
+        &s,
+        seps,
+        quote! {
+            #[cfg(feature = "logging")]
+            ::log::debug!("Parser is inside {}", #s);
+            #(#tries)*
+            // report the tag of the segment none of the variants accepted
+            let tag = tokens.first().map_or("", |t| t.tag);
+            ::core::result::Result::Err(::nom::Err::Error(#util::EdifactError::new(#expected, tag)))
+        },
+        quote! { false #(|| #starts)* },
+        quote! { false #(|| <#types>::knows_tag(tag))* },
+    )
+}
+
+/// Implements `parse_tokens`, `can_start` and `knows_tag` of groups, messages and choices with `Parser` on top.
+///
+/// `body` advances over the segments in `tokens`, `parse_with` splits its input into segments
+/// once and hands back the text of the segments left over.
+fn gen_token_parser(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    label: &str,
+    seps: TokenStream,
+    body: TokenStream,
+    can_start: TokenStream,
+    knows_tag: TokenStream,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let Input {
+        lt,
+        borrowed,
+        impl_generics,
+        fn_lts,
+        ty,
+    } = input(ast)?;
+    let offset = gen_unwrapped_offset();
+    // once a segment spans a line break the input is tokenized again without line breaks, as a release
+    // character in front of a break releases what follows it. The spans of the segments, errors,
+    // diagnostics and warnings pointing into the copy are moved back into the input.
+    let in_order = quote! {
+        {
+            let (rest, output) = #name::parse_tokens(&tokens, ctx, ::core::option::Option::None)?;
+            let end = unterminated.as_ptr() as usize - input.as_ptr() as usize;
+            (tokens.get(tokens.len() - rest.len()).map_or(end, |t| t.span.start), output)
+        }
+    };
+    let parse = if borrowed {
+        quote! {
+            if let ::core::option::Option::Some(t) = tokens.iter().find(|t| wrapped(t)) {
+                return ::core::result::Result::Err(::nom::Err::Failure(#util::EdifactError::new("segment without line breaks", t.raw)));
+            }
+            let (rest, output) = #in_order;
+        }
+    } else {
+        quote! {
+            let (rest, output) = if tokens.iter().any(|t| wrapped(t)) {
+                let offset = #offset;
+                let joined: ::std::string::String = input.chars().filter(|&c| c != '\r' && c != '\n').collect();
+                let relocate = |e: #util::EdifactError| e.relocate(&joined, input, |at| offset(input, at));
+                let (tokens, unterminated) = #util::tokenize(&joined, ctx);
+                let tokens: ::std::vec::Vec<_> = tokens
+                    .into_iter()
+                    .map(|t| {
+                        let span = #util::Span::new(
+                            offset(input, t.span.start),
+                            offset(input, t.span.end.saturating_sub(1)) + usize::from(t.span.end > t.span.start),
+                            t.span.segment,
+                        );
+                        #util::RawSegment { span, ..t }
+                    })
+                    .collect();
+                let result = #name::parse_tokens(&tokens, ctx, ::core::option::Option::None);
+                for e in ctx.take_diagnostics() {
+                    ctx.report(relocate(e));
+                }
+                for e in ctx.take_warnings() {
+                    ctx.warn(relocate(e));
+                }
+                let (rest, output) = result.map_err(|e| e.map(relocate))?;
+                let end = offset(input, joined.len() - unterminated.len());
+                (tokens.get(tokens.len() - rest.len()).map_or(end, |t| t.span.start), output)
+            } else #in_order;
+        }
+    };
+    let parser = gen_parser(
+        ast,
+        util,
+        label,
+        seps,
+        quote! {
+            let (tokens, unterminated) = #util::tokenize(input, ctx);
+            let wrapped = |t: &#util::RawSegment<'_>| ctx.wrapped && t.raw.contains(['\r', '\n']);
+            #parse
+            ::core::result::Result::Ok((&input[rest..], output))
+        },
+    )?;
+    Ok(quote! {
+        #parser
+        impl #impl_generics #ty {
+            pub fn can_start(
+                tokens: &[#util::RawSegment<'_>],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> bool {
+                #can_start
+            }
+
+            pub fn knows_tag(tag: &str) -> bool {
+                #knows_tag
+            }
+
+            pub fn parse_tokens<#fn_lts 't>(
+                tokens: &'t [#util::RawSegment<#lt>],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> ::nom::IResult<&'t [#util::RawSegment<#lt>], #ty, #util::EdifactError> {
+                #body
+            }
+        }
+    })
+}
+
 // impl<'a> Parser<&'a str, C002, nom::error::Error<&'a str>> for C002 {
 //     fn parse(input: &'a str) -> IResult<&'a str, C002> {
 //         let (_, vars) = crate::util::parse_colon_section(input)?;
@@ -384,7 +1471,7 @@ fn generate_sg_parser(ast: &DeriveInput, is_sg: bool) -> syn::Result<TokenStream
 //     }
 // }
 
-#[proc_macro_derive(ParseSegment)]
+#[proc_macro_derive(ParseSegment, attributes(edifact))]
 pub fn parse_segment(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let output = generate_segment_parser(&input).unwrap_or_else(|err| err.to_compile_error());
@@ -404,136 +1491,583 @@ pub fn parse_segment(input: proc_macro::TokenStream) -> proc_macro::TokenStream
 // }
 
 fn generate_segment_parser(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    reject_unknown(ast)?;
+    let util = util_path(ast)?;
     let name = &ast.ident;
-    let tok = parse_all(ast);
-    let s = format_ident!("{}", name).to_string().to_uppercase();
-    let res = quote! {
-        impl<'a> crate::util::Parser<&'a str, #name, nom::error::Error<&'a str>> for #name {
-            fn parse(input: &'a str) -> ::nom::IResult<&'a str, #name> {
+    let tok = parse_all(ast, &util, false)?;
+    let idents = fields(ast)?.into_iter().map(|f| f.ident);
+    let surplus = gen_surplus(ast, &util, false)?;
+    let s = segment_tag(ast)?;
+    // on text the span counts from the start of `input`, tokens know their place in the interchange
+    let span = span_field(ast)?;
+    let default_span = span.as_ref().map(|id| {
+        quote! { #id: ::core::default::Default::default(), }
+    });
+    let skip = match span {
+        Some(_) => quote! {
+            let skipped = input.len() - input.trim_start_matches(|c| ctx.whitespace.contains(c)).len();
+            let input = &input[skipped..];
+        },
+        None => quote! {
+            let input = input.trim_start_matches(|c| ctx.whitespace.contains(c));
+        },
+    };
+    let text_span = span.as_ref().map(|id| {
+        quote! {
+            let consumed = input[..input.len() - output_rest.len()]
+                .trim_end_matches(|c| ctx.whitespace.contains(c))
+                .len();
+            let output = #name { #id: #util::Span::new(skipped, skipped + consumed, 1), ..output };
+        }
+    });
+    let token_span = span.as_ref().map(|id| {
+        quote! {
+            let output = #name { #id: token.span, ..output };
+        }
+    });
+    let qualifier = match ContainerAttrs::from_ast(ast)?.qualifier {
+        Some(q) => quote! { ::core::option::Option::Some(#q) },
+        None => quote! { ::core::option::Option::None },
+    };
+    let unwrap = gen_unwrap_text(ast, &util, span.as_ref())?;
+    let res = gen_parser(
+        ast,
+        &util,
+        &s,
+        quote! { #util::Separators::default() },
+        quote! {
+            #unwrap
+            #name::parse_with_qualifier(input, ctx, #qualifier)
+        },
+    )?;
+    let into_owned = gen_into_owned(ast)?;
+    let Input {
+        lt,
+        impl_generics,
+        fn_lts,
+        ty,
+        ..
+    } = input(ast)?;
+    Ok(quote! {
+        #res
+        #into_owned
+        impl #impl_generics #ty {
+            pub fn can_start(
+                tokens: &[#util::RawSegment<'_>],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> bool {
+                let ::core::option::Option::Some(token) = tokens.first() else {
+                    return false;
+                };
+                // the qualifier is the first component of the first element
+                token.tag == #s
+                    && qualifier.or(#qualifier).is_none_or(|qualifier| {
+                        token.elements.first().is_some_and(|e| {
+                            &e[..e.find(ctx.component).unwrap_or(e.len())] == qualifier
+                        })
+                    })
+            }
+
+            pub fn knows_tag(tag: &str) -> bool {
+                tag == #s
+            }
+
+            pub fn parse_with_qualifier<#fn_lts>(
+                input: &#lt str,
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> ::nom::IResult<&#lt str, #ty, #util::EdifactError> {
                 #[cfg(feature = "logging")]
-                log::debug!("Parser is inside {}", #s);
-                let (output_rest, vars) = crate::util::parse_line(input, #s)?;
+                ::log::debug!("Parser is inside {}", #s);
+                // whitespace between segments is skipped
+                #skip
+                let (output_rest, vars) = #util::parse_line_released(input, #s, ctx)
+                    .map_err(|e| e.map(|e| #util::EdifactError::new(#s, e.input)))?;
+                let output_rest = output_rest.trim_start_matches(|c| ctx.whitespace.contains(c));
                 #[cfg(feature = "logging")]
-                log::debug!("Variables created {vars:?}");
+                ::log::debug!("Left over string {output_rest:?}");
+                let output = #name::from_elements(input, &vars, ctx, qualifier)?;
+                #text_span
+                ::core::result::Result::Ok((output_rest, output))
+            }
+
+            pub fn parse_tokens<#fn_lts 't>(
+                tokens: &'t [#util::RawSegment<#lt>],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> ::nom::IResult<&'t [#util::RawSegment<#lt>], #ty, #util::EdifactError> {
+                match tokens.split_first() {
+                    ::core::option::Option::Some((token, rest)) if token.tag == #s => {
+                        // fields without a peek rely on the container qualifier being checked here
+                        let output = #name::from_elements(token.raw, &token.elements, ctx, qualifier.or(#qualifier))?;
+                        #token_span
+                        ::core::result::Result::Ok((rest, output))
+                    }
+                    ::core::option::Option::Some((token, _)) => ::core::result::Result::Err(::nom::Err::Error(#util::EdifactError::new(#s, token.tag))),
+                    ::core::option::Option::None => ::core::result::Result::Err(::nom::Err::Error(#util::EdifactError::new(#s, ""))),
+                }
+            }
+
+            // `input` is the segment, `vars` are its data elements after the tag
+            fn from_elements<#fn_lts>(
+                input: &#lt str,
+                vars: &[&#lt str],
+                ctx: &#util::ParseContext,
+                qualifier: ::core::option::Option<&str>,
+            ) -> ::core::result::Result<#ty, ::nom::Err<#util::EdifactError>> {
+                // the qualifier is the first component of the first element
+                if let ::core::option::Option::Some(qualifier) = qualifier {
+                    let found = vars
+                        .first()
+                        .and_then(|e| #util::split_released(e, ctx.component, ctx).first().copied())
+                        .unwrap_or(&input[..0]);
+                    if found != qualifier {
+                        return ::core::result::Result::Err(::nom::Err::Error(
+                            #util::EdifactError::new(&::std::format!("qualifier {}", qualifier), found).at_element(1),
+                        ));
+                    }
+                }
                 #[cfg(feature = "logging")]
-                log::debug!("Left over string {output_rest:?}");
-                let output = #name {
-                    #(#tok)*
-                };
-                Ok((output_rest, output))
+                ::log::debug!("Variables created {vars:?}");
+                #surplus
+                #(#tok)*
+                ::core::result::Result::Ok(#name {
+                    #(#idents,)*
+                    #default_span
+                })
             }
         }
+    })
+}
+
+/// Implements `Parser` on top of `parse_with`.
+///
+/// Only `parse` prefixes errors with `label` and locates them in the input,
+/// nested parsers call `parse_with` and add the label of the failing field.
+fn gen_parser(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    label: &str,
+    seps: TokenStream,
+    body: TokenStream,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let Input {
+        lt,
+        impl_generics,
+        fn_lts,
+        ty,
+        ..
+    } = input(ast)?;
+    Ok(quote! {
+        impl<#lt> #util::Parser<&#lt str, #ty, #util::EdifactError> for #ty {
+            fn parse(input: &#lt str) -> ::nom::IResult<&#lt str, #ty, #util::EdifactError> {
+                let ctx = #util::ParseContext::new(#seps);
+                #name::parse_with(input, &ctx).map_err(|e| e.map(|e| e.within(#label).locate(input, &ctx)))
+            }
+        }
+        impl #impl_generics #ty {
+            pub fn parse_with<#fn_lts>(input: &#lt str, ctx: &#util::ParseContext) -> ::nom::IResult<&#lt str, #ty, #util::EdifactError> {
+                #body
+            }
+        }
+    })
+}
+
+/// Byte offset in `original` of the byte at `at` in the text `original` without line breaks, for
+/// moving errors and spans found in the rejoined text back to where they are in the input.
+fn gen_unwrapped_offset() -> TokenStream {
+    quote! {
+        |original: &str, at: usize| {
+            let mut kept = 0;
+            original
+                .char_indices()
+                .filter(|&(_, c)| c != '\r' && c != '\n')
+                .find(|&(_, c)| {
+                    let reached = kept == at;
+                    kept += c.len_utf8();
+                    reached
+                })
+                .map_or(original.len(), |(i, _)| i)
+        }
+    }
+}
+
+/// Rejoins a segment or composite spanning a line break when `ctx.wrapped` is set.
+///
+/// The input without line breaks is parsed instead, errors, the span and the rest are moved back
+/// into `input`. Borrowing structs cannot hold the rejoined values and fail on such a segment.
+/// Input with line breaks only between segments is parsed as it is.
+fn gen_unwrap_text(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    span: Option<&Ident>,
+) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let offset = gen_unwrapped_offset();
+    let span = span.map(|id| {
+        quote! {
+            let span = output.#id;
+            let output = #name {
+                #id: #util::Span::new(
+                    offset(input, span.start),
+                    offset(input, span.end.saturating_sub(1)) + usize::from(span.end > span.start),
+                    span.segment,
+                ),
+                ..output
+            };
+        }
+    });
+    let unwrap = if input(ast)?.borrowed {
+        quote! {
+            return ::core::result::Result::Err(::nom::Err::Failure(#util::EdifactError::new("segment without line breaks", segment)));
+        }
+    } else {
+        quote! {
+            let offset = #offset;
+            let joined: ::std::string::String = input.chars().filter(|&c| c != '\r' && c != '\n').collect();
+            let (rest, output) = #name::parse_with(&joined, ctx)
+                .map_err(|e| e.map(|e| e.relocate(&joined, input, |at| offset(input, at))))?;
+            #span
+            return ::core::result::Result::Ok((&input[offset(input, joined.len() - rest.len())..], output));
+        }
     };
-    Ok(res)
+    Ok(quote! {
+        if ctx.wrapped {
+            // up to the first terminator that is not released, a release character in front of a line
+            // break releases what follows the break
+            let segment = input.trim_start_matches(|c| ctx.whitespace.contains(c));
+            let mut released = false;
+            let end = segment
+                .char_indices()
+                .filter(|&(_, c)| c != '\r' && c != '\n')
+                .find(|&(_, c)| {
+                    let end = !released && c == ctx.segment;
+                    released = !released && c == ctx.release;
+                    end
+                })
+                .map_or(segment.len(), |(i, _)| i);
+            let segment = &segment[..end];
+            if segment.contains(['\r', '\n']) {
+                #unwrap
+            }
+        }
+    })
 }
 
-fn parse_all(ast: &DeriveInput) -> Vec<TokenStream> {
-    let x = &ast.data;
+/// Binds every field to a local variable of the same name.
+///
+/// Errors inside a matched segment or composite are returned as `nom::Err::Failure`
+/// pointing at the offending value, so group parsers do not backtrack over them.
+/// `component` tells whether the fields are components of a composite or elements of a segment.
+fn parse_all(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    component: bool,
+) -> syn::Result<Vec<TokenStream>> {
     let name = format!("{}", &ast.ident);
     let mut output = vec![];
-    if let Data::Struct(s) = x {
-        let f = &s.fields;
-        for (idx, o) in f.into_iter().enumerate() {
-            // _010, _020, etc
-            let struct_field = o.ident.clone().unwrap();
-            let sf_string = struct_field.to_string();
-            let syn::Type::Path(tp) = &o.ty else {
-                panic!("Path type not found!")
-            };
-            let s = tp.path.segments.first().unwrap();
-            let opt_vec = s.ident.clone();
-            let ov_string = opt_vec.to_string();
-            match opt_vec.to_string().as_str() {
-                "Option" | "Vec" => {
-                    // List, String, Segment inside option or vec
-                    let mut inside_opt_vec: Ident = Ident::new("placeholder", Span::call_site());
-                    if let PathArguments::AngleBracketed(abga) = &s.arguments {
-                        if let GenericArgument::Type(syn::Type::Path(tp)) =
-                            abga.args.first().unwrap()
-                        {
-                            inside_opt_vec = tp.path.segments.first().unwrap().ident.clone();
+    for (idx, field) in fields(ast)?.iter().enumerate() {
+        // _010, _020, etc
+        let struct_field = &field.ident;
+        let sf_string = struct_field.to_string();
+        // position of the element or component, counted from 1
+        let pos = idx + 1;
+        let at = if component {
+            quote! { at_component(#pos) }
+        } else {
+            quote! { at_element(#pos) }
+        };
+        let label = field.label();
+        // `String`, `&'a str` and `Cow<'a, str>` all convert from the escaped slice
+        if field.attrs.rest {
+            output.push(quote! {
+                let #struct_field = vars
+                    .iter()
+                    .skip(#idx)
+                    .map(|&x| ::core::convert::From::from(x))
+                    .collect::<::std::vec::Vec<_>>();
+            });
+            continue;
+        }
+        let item = gen_parse_value(field, util, &name, &sf_string);
+        match field.shape {
+            Shape::Vec => {
+                // repeating data element, items are split at the repetition separator
+                let max = field.attrs.max_repeat.map(|max| {
+                    let expected = format!("at most {max} repetitions");
+                    quote! {
+                        if items.len() > #max {
+                            #[cfg(feature = "logging")]
+                            ::log::error!("Line: {input}\nFor struct {}, {} repeats {} times, at most {} are allowed", #name, #sf_string, items.len(), #max);
+                            return ::core::result::Result::Err(::nom::Err::Failure(
+                                #util::EdifactError::new(#expected, vars[#idx]).within(#label).#at,
+                            ));
                         }
                     }
-                    let iov_string = inside_opt_vec.to_string();
-
-                    // Can be String, _XXX (List), or CXXX,SXXX (Segment)
-                    if inside_opt_vec == "String" {
-                        output.push(quote! {
-                            #struct_field: vars.get(#idx).map(|x| x.to_string()),
-                        });
-                    } else if inside_opt_vec.to_string().starts_with('_') {
-                        // List (types.rs)
-                        output.push(quote! {
-                                #struct_field: vars.get(#idx).filter(|&f| !f.is_empty()).map(|x| match #inside_opt_vec::from_str(clean_num(x)) {
-                                    Ok(f) => f,
-                                    Err(e) => {
-                                        #[cfg(feature = "logging")]
-                                        log::error!("Line: {input}\nFor struct {}, parsing optional list item {} failed. Enum {} encountered the following error: {}", #name, #sf_string, #iov_string, e);
-                                        panic!("Parsing optional list item failed");
-                                    },
-                                }),
-                            });
-                    } else {
-                        // Segment or Element
-                        output.push(quote! {
-                                #struct_field: vars.get(#idx).filter(|&f| !f.is_empty()).map(|x| match #inside_opt_vec::parse(x) {
-                                    Ok((_,r)) => r,
-                                    Err(e) => {
-                                        #[cfg(feature = "logging")]
-                                        log::error!("Line: {input}\nFor struct {}, parsing optional segment or element {} failed. Struct {} encountered the following error: {}", #name, #sf_string, #iov_string, e);
-                                        panic!("Parsing optional segment or element failed");
-                                    },
-                                }),
-                            });
-                    }
+                });
+                output.push(quote! {
+                    let #struct_field = {
+                        let items = vars
+                            .get(#idx)
+                            .filter(|&f| !f.is_empty())
+                            .map(|&x| #util::split_released(x, ctx.repetition, ctx))
+                            .unwrap_or_default();
+                        #max
+                        items
+                            .into_iter()
+                            .map(|x| #item)
+                            .collect::<::core::result::Result<::std::vec::Vec<_>, _>>()
+                            .map_err(|e| e.map(|e| e.within(#label).#at))?
+                    };
+                });
+            }
+            Shape::Option => {
+                output.push(quote! {
+                    let #struct_field = vars
+                        .get(#idx)
+                        .filter(|&f| !f.is_empty())
+                        .map(|&x| #item)
+                        .transpose()
+                        .map_err(|e| e.map(|e| e.within(#label).#at))?;
+                });
+            }
+            Shape::Plain => {
+                output.push(quote! {
+                    let #struct_field = match vars.get(#idx).filter(|&f| !f.is_empty()) {
+                        ::core::option::Option::Some(&x) => #item.map_err(|e| e.map(|e| e.within(#label).#at))?,
+                        ::core::option::Option::None => {
+                            #[cfg(feature = "logging")]
+                            ::log::error!("Line: {input}\nFor struct {}, mandatory {} was not found", #name, #sf_string);
+                            let at = vars.get(#idx).or(vars.last()).map_or(&input[..0], |v| &v[v.len()..]);
+                            return ::core::result::Result::Err(::nom::Err::Failure(
+                                #util::EdifactError::new("mandatory value", at).within(#label).#at,
+                            ));
+                        }
+                    };
+                });
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Rejects non-empty values in `vars` beyond the fields when `ctx.strict` is set.
+///
+/// Lenient parsing ignores them, empty trailing values are accepted either way.
+/// A struct with a `rest` field keeps them instead.
+fn gen_surplus(ast: &DeriveInput, util: &TokenStream, component: bool) -> syn::Result<TokenStream> {
+    let Some(surplus) = find_surplus(ast, util, component, quote! { vars })? else {
+        return Ok(quote! {});
+    };
+    Ok(quote! {
+        if ctx.strict {
+            if let ::core::option::Option::Some(e) = #surplus {
+                return ::core::result::Result::Err(::nom::Err::Failure(e));
+            }
+        }
+    })
+}
+
+/// The error for the first non-empty value in `vars` beyond the fields, an `Option<EdifactError>`.
+///
+/// `None` for a struct with a `rest` field, which keeps them.
+fn find_surplus(
+    ast: &DeriveInput,
+    util: &TokenStream,
+    component: bool,
+    vars: TokenStream,
+) -> syn::Result<Option<TokenStream>> {
+    let fields = fields(ast)?;
+    if fields.iter().any(|f| f.attrs.rest) {
+        return Ok(None);
+    }
+    let count = fields.len();
+    let (expected, at) = if component {
+        (
+            format!("at most {count} components"),
+            quote! { at_component },
+        )
+    } else {
+        (format!("at most {count} elements"), quote! { at_element })
+    };
+    Ok(Some(quote! {
+        #vars.iter().skip(#count).position(|v| !v.is_empty()).map(|pos| {
+            let pos = #count + pos;
+            #util::EdifactError::new(#expected, #vars[pos]).#at(pos + 1)
+        })
+    }))
+}
+
+/// Converts the raw value `x` into the field type, the expression evaluates to a `Result`.
+fn gen_parse_value(
+    field: &EdiField,
+    util: &TokenStream,
+    name: &str,
+    sf_string: &str,
+) -> TokenStream {
+    let ty = &field.ty;
+    let label = field.label();
+    match field.kind {
+        Kind::Text => {
+            let text = gen_text(field, util);
+            // a slice of the input cannot leave out the release characters
+            let escaped = (field.text == Text::Borrowed).then(|| {
+                quote! {
+                    if x.contains(ctx.release) {
+                        ::core::result::Result::Err(::nom::Err::Failure(#util::EdifactError::new("text without release character", x)))
+                    } else
                 }
-                "String" => {
-                    output.push(quote! {
-                        #struct_field: match vars.get(#idx).filter(|&f| !f.is_empty()).map(|x| x.to_string()) {
-                            Some(f) => f,
-                            None => {
-                                #[cfg(feature = "logging")]
-                                log::error!("Line: {input}\nFor struct {}, parsing mandatory {}.to_string() was not found", #name, #sf_string);
-                                panic!("Parsing mandatory to_string() failed");
-                        },
-                        },
-                    });
-                }
-                _ => {
-                    // Can be _XXX (List), or CXXX,SXXX (Segment)
-                    if opt_vec.to_string().starts_with('_') {
-                        // List (types.rs)
-                        output.push(quote! {
-                            #struct_field: vars.get(#idx).filter(|&f| !f.is_empty()).map(|x|match #opt_vec::from_str(clean_num(x)){
-                                Ok(f) => f,
-                                Err(e) => {
-                                    #[cfg(feature = "logging")]
-                                    log::error!("Line: {input}\nFor struct {}, parsing list item {} failed. Enum {} encountered the following error: {}", #name, #sf_string, #ov_string, e);
-                                    panic!("Parsing list item failed");
-                                },
-                            }).expect("Parsing List: not found"),
-                        });
-                    } else {
-                        // Segment or Element
-                        output.push(quote! {
-                            #struct_field: vars.get(#idx).filter(|&f| !f.is_empty()).map(|x| match #opt_vec::parse(x) {
-                                Ok((_,r)) => r,
-                                Err(e) => {
-                                    #[cfg(feature = "logging")]
-                                    log::error!("Line: {input}\nFor struct {}, parsing segment or element {} failed. Struct {} encountered the following error: {}", #name, #sf_string, #ov_string, e);
-                                    panic!("Parsing list item failed");
-                                },
-                            }).expect("Parsing Segement or Element: not found"),
-                        });
-                    }
+            });
+            quote! {
+                #escaped {
+                    ::core::result::Result::<_, ::nom::Err<#util::EdifactError>>::Ok(#text)
                 }
             }
         }
+        Kind::Code => {
+            let expected = format!("code of {label}");
+            quote! {
+                <#ty as ::core::str::FromStr>::from_str(#util::clean_num(&#util::unescape(x, ctx))).map_err(|e| {
+                    #[cfg(feature = "logging")]
+                    ::log::error!("Line: {input}\nFor struct {}, parsing list item {} failed. Enum {} encountered the following error: {}", #name, #sf_string, #label, e);
+                    #[cfg(not(feature = "logging"))]
+                    let _ = e;
+                    ::nom::Err::Failure(#util::EdifactError::new(#expected, x))
+                })
+            }
+        }
+        Kind::Composite => quote! {
+            <#ty>::parse_with(x, ctx).map(|(_, r)| r).map_err(|e| {
+                #[cfg(feature = "logging")]
+                ::log::error!("Line: {input}\nFor struct {}, parsing segment or element {} failed. Struct {} encountered the following error: {}", #name, #sf_string, #label, e);
+                match e {
+                    ::nom::Err::Error(e) => ::nom::Err::Failure(e),
+                    e => e,
+                }
+            })
+        },
+        Kind::Value => quote! {
+            <#ty as #util::ParseValue>::parse_value(x, ctx).map_err(::nom::Err::Failure)
+        },
+    }
+}
+
+/// Converts the raw text `x`, a slice of the input, into the type of a text field.
+///
+/// `&'a str` fields take the slice, which has nothing to unescape, `Cow<'a, str>` fields only
+/// allocate when there is something to unescape.
+fn gen_text(field: &EdiField, util: &TokenStream) -> TokenStream {
+    match field.text {
+        Text::Owned => quote! { #util::unescape(x, ctx) },
+        Text::Borrowed => quote! { x },
+        Text::Cow => quote! {
+            if x.contains(ctx.release) {
+                ::std::borrow::Cow::Owned(#util::unescape(x, ctx))
+            } else {
+                ::std::borrow::Cow::Borrowed(x)
+            }
+        },
     }
-    output
 }
 
-#[proc_macro_derive(ParseOuterSegment)]
+/// Implements `into_owned` for a struct or choice borrowing from its input.
+///
+/// Left out for structs with `&'a str` fields or unknown segments, those cannot outlive the input.
+/// Nested types with a lifetime are expected to provide `into_owned` as well.
+fn gen_into_owned(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let Input {
+        borrowed,
+        impl_generics,
+        ty,
+        ..
+    } = input(ast)?;
+    if !borrowed || ContainerAttrs::from_ast(ast)?.no_into_owned {
+        return Ok(quote! {});
+    }
+    let name = &ast.ident;
+    let body = match variants(ast)? {
+        Some(variants) => {
+            let arms = variants.iter().map(|v| {
+                let ident = &v.ident;
+                let value = if has_lifetime(&v.ty) {
+                    quote! { value.into_owned() }
+                } else {
+                    quote! { value }
+                };
+                quote! {
+                    #name::#ident(value) => #name::#ident(#value),
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        None => {
+            let fields = fields(ast)?;
+            // neither can the unknown segments
+            if fields.iter().any(|f| f.text == Text::Borrowed) || unknown_field(ast)?.is_some() {
+                return Ok(quote! {});
+            }
+            let values = fields.iter().map(|f| {
+                let id = &f.ident;
+                let owned = if f.text == Text::Cow {
+                    quote! { ::std::borrow::Cow::Owned(x.into_owned()) }
+                } else if has_lifetime(&f.ty) {
+                    quote! { x.into_owned() }
+                } else {
+                    return quote! { #id: self.#id };
+                };
+                match f.shape {
+                    Shape::Plain => quote! { #id: { let x = self.#id; #owned } },
+                    Shape::Option => quote! { #id: self.#id.map(|x| #owned) },
+                    Shape::Vec => quote! { #id: self.#id.into_iter().map(|x| #owned).collect() },
+                }
+            });
+            let span = span_field(ast)?.map(|id| quote! { #id: self.#id, });
+            quote! {
+                #name {
+                    #(#values,)*
+                    #span
+                }
+            }
+        }
+    };
+    Ok(quote! {
+        impl #impl_generics #ty {
+            pub fn into_owned(self) -> #name<'static> {
+                #body
+            }
+        }
+    })
+}
+
+/// Composites are no segments of their own, they have no span.
+fn reject_span(ast: &DeriveInput) -> syn::Result<()> {
+    match span_field(ast)? {
+        Some(id) => Err(syn::Error::new_spanned(
+            id,
+            "span only applies to segments, groups and messages",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// `FromStr` cannot borrow from its argument, `instead` names the derive to use.
+fn reject_borrowed(ast: &DeriveInput, instead: &str) -> syn::Result<()> {
+    if input(ast)?.borrowed {
+        return Err(syn::Error::new_spanned(
+            &ast.generics,
+            format!("FromStr cannot borrow from its input, derive {instead} instead"),
+        ));
+    }
+    Ok(())
+}
+
+#[proc_macro_derive(ParseOuterSegment, attributes(edifact))]
 pub fn parse_outer(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
     let toks = generate_outer_parse(&ast).unwrap_or_else(|err| err.to_compile_error());
@@ -541,105 +2075,88 @@ pub fn parse_outer(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 }
 
 fn generate_outer_parse(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    reject_unknown(ast)?;
+    let util = util_path(ast)?;
+    reject_borrowed(ast, "ParseSegment")?;
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let output = gen_outer_props(ast);
-    let prop_count = output.len();
-    let upper_name = format_ident!("{}", name).to_string().to_uppercase();
+    let output = gen_outer_props(ast, &util)?;
+    // a `rest` field takes the surplus elements, empty trailing ones are accepted
+    let surplus = find_surplus(ast, &util, false, quote! { parts[1..] })?.map(|surplus| {
+        quote! {
+            if let ::core::option::Option::Some(e) = #surplus {
+                return ::core::result::Result::Err(e);
+            }
+        }
+    });
+    let upper_name = segment_tag(ast)?;
     Ok(quote! {
         impl #impl_generics ::core::str::FromStr for #name #ty_generics #where_clause {
-            type Err = ParseError;
-
-            fn from_str(s: &str) -> Result<Self, Self::Err> {
-                let x = s.trim_end_matches('\'');
-                let parts: Vec<&str> = x.split('+').collect();
-                if parts[0] == #upper_name {
-                    if parts.len() > #prop_count +1  {
-                        Err(ParseError {
-                            msg: "too many segments".to_string(),
-                        })
-                    } else {
+            type Err = #util::EdifactError;
+
+            fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                let ctx = #util::ParseContext::new(#util::Separators::default()).strict();
+                let seps: &#util::Separators = &ctx;
+                let parse = || {
+                    let x = #util::split_released(s, seps.segment, &seps).into_iter().next().unwrap_or_default();
+                    let parts: ::std::vec::Vec<&str> = #util::split_released(x, seps.element, &seps);
+                    let tag = parts.first().copied().unwrap_or(x);
+                    if tag == #upper_name {
+                        #surplus
                         let mut obj = #name::default();
                         #(#output)*
-                        Ok(obj)
+                        ::core::result::Result::Ok(obj)
+                    } else {
+                        ::core::result::Result::Err(#util::EdifactError::new(#upper_name, tag))
                     }
-                } else {
-                    Err(ParseError {
-                        msg: "segment name wrong".to_string(),
-                    })
-                }
+                };
+                parse().map_err(|e| e.within(#upper_name).locate(s, &seps))
             }
         }
     })
 }
 
-fn gen_outer_props(ast: &DeriveInput) -> Vec<TokenStream> {
-    let x = &ast.data;
+fn gen_outer_props(ast: &DeriveInput, util: &TokenStream) -> syn::Result<Vec<TokenStream>> {
     let mut output = vec![];
-    let mut idx: usize = 1;
-    if let Data::Struct(s) = x {
-        let f = &s.fields;
-        for o in f {
-            let id = o.ident.clone().unwrap();
-            let t = &o.ty;
-            if let syn::Type::Path(p) = t {
-                let s = &p.path.segments;
-                let we = s.first().unwrap();
-                let w = &we.ident;
-                let arg = &we.arguments;
-                let sub_id = &we.ident;
-                let ty = w.to_string();
-                match ty.as_str() {
-                    "Option" => {
-                        // look for type inside
-                        if let PathArguments::AngleBracketed(c) = arg {
-                            let o = c.args.first().unwrap();
-                            if let syn::GenericArgument::Type(syn::Type::Path(tpo)) = o {
-                                let sg = tpo.path.segments.first().unwrap();
-                                let ident = &sg.ident;
-                                let type_name = ident.to_string();
-                                match type_name.as_str() {
-                                    "String" => {
-                                        let chunk = quote! {
-                                            if let Some(val) = parts.get(#idx) {
-                                                obj.#id = Some(val.to_string());
-                                            }
-                                        };
-                                        output.push(chunk);
-                                    }
-                                    _ => {
-                                        let chunk = quote! {
-                                            if let Some(val) = parts.get(#idx) {
-                                                let t = #ident::from_str(val).unwrap();
-                                                obj.#id = Some(t);
-                                            }
-                                        };
-                                        output.push(chunk);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    "String" => {
-                        let chunk = quote! {
-                            if let Some(val) = parts.get(#idx) {
-                                obj.#id = val.to_string();
-                            }
-                        };
-                        output.push(chunk);
-                    }
-                    _ => {
-                        let chunk = quote! {
-                            if let Some(val) = parts.get(#idx) {
-                                obj.#id = #sub_id::from_str(val).unwrap();
-                            }
-                        };
-                        output.push(chunk);
+    for (i, field) in fields(ast)?.iter().enumerate() {
+        let id = &field.ident;
+        // the tag is part 0
+        let idx = i + 1;
+        let at = quote! { at_element(#idx) };
+        if field.attrs.rest {
+            output.push(quote! {
+                obj.#id = parts.iter().skip(#idx).map(|&x| ::core::convert::From::from(x)).collect();
+            });
+            continue;
+        }
+        let item = gen_from_str_value(field, util, &at);
+        let chunk = match field.shape {
+            Shape::Vec => {
+                let max = gen_max_repeat(field, util, quote! { val }, &at);
+                quote! {
+                    if let ::core::option::Option::Some(val) = parts.get(#idx).filter(|&f| !f.is_empty()) {
+                        let items = #util::split_released(val, seps.repetition, &seps);
+                        #max
+                        obj.#id = items.into_iter().map(|x| #item).collect::<::core::result::Result<::std::vec::Vec<_>, _>>()?;
                     }
                 }
             }
-            idx += 1;
-        }
+            Shape::Option => quote! {
+                if let ::core::option::Option::Some(&x) = parts.get(#idx).filter(|&f| !f.is_empty()) {
+                    obj.#id = ::core::option::Option::Some(#item?);
+                }
+            },
+            Shape::Plain => {
+                let missing = gen_missing(field, util, idx, &at);
+                quote! {
+                    obj.#id = match parts.get(#idx).filter(|&f| !f.is_empty()) {
+                        ::core::option::Option::Some(&x) => #item?,
+                        ::core::option::Option::None => #missing,
+                    };
+                }
+            }
+        };
+        output.push(chunk);
     }
-    output
+    Ok(output)
 }
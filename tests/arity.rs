@@ -0,0 +1,57 @@
+mod types;
+mod util;
+
+use edifact_types_macros::Edifact;
+use util::{ParseContext, Parser, Separators};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C506 {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct RFF {
+    #[edifact(composite)]
+    pub _010: C506,
+}
+
+#[test]
+fn strict_and_lenient() {
+    let lenient = ParseContext::new(Separators::default());
+    let strict = ParseContext::new(Separators::default()).strict();
+    let (_, rff) = RFF::parse_with("RFF+ON:1:x+y'", &lenient).unwrap();
+    assert_eq!(rff._010._020.as_deref(), Some("1"));
+    assert!(RFF::parse("RFF+ON:1:x+y'").is_ok());
+    // trailing empty values are fine in strict mode too
+    assert!(RFF::parse_with("RFF+ON:1:++'", &strict).is_ok());
+    let e = match RFF::parse_with("RFF+ON:1++y'", &strict) {
+        Err(nom::Err::Failure(e)) => e,
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(e.expected, "at most 1 elements");
+    assert_eq!(e.element, Some(3));
+    let e = "RFF+ON:1:x".parse::<RFF>().unwrap_err();
+    assert_eq!(e.path(), "RFF/C506");
+    assert_eq!(e.expected, "at most 2 components");
+    assert_eq!((e.element, e.component), (Some(1), Some(3)));
+    assert_eq!(e.found, "x");
+}
+
+#[test]
+fn from_str_accepts_empty_trailing() {
+    assert_eq!(
+        "a:b:c:".parse::<types::C080>().unwrap()._030.as_deref(),
+        Some("c")
+    );
+    let e = "a:b:c::d".parse::<types::C080>().unwrap_err();
+    assert_eq!(e.expected, "at most 3 components");
+    assert_eq!((e.component, e.found.as_str()), (Some(5), "d"));
+    let nad = "NAD+BY++x+y++".parse::<types::NAD>().unwrap();
+    assert_eq!(nad._040.as_deref(), Some("y"));
+    let e = "NAD+BY++x+y++z".parse::<types::NAD>().unwrap_err();
+    assert_eq!(e.expected, "at most 4 elements");
+    assert_eq!((e.element, e.found.as_str()), (Some(6), "z"));
+}
@@ -0,0 +1,160 @@
+mod types;
+mod util;
+
+use types::*;
+use util::Parser;
+
+#[test]
+fn roundtrip_msg() {
+    let s = "BGM+a+b'\nNAD+BY+x+c1:c2'\nDTM+1'\nNAD+SU'";
+    let (rest, m) = MSG::parse(s).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(m.sg1.len(), 2);
+    assert_eq!(m.to_string(), "BGM+a+b'\nNAD+BY+x+c1:c2'\nDTM+1'\nNAD+SU'");
+    let mut out = Vec::new();
+    m.write_to(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), m.to_string());
+}
+
+#[test]
+fn display_escapes_values() {
+    let nad = NAD {
+        _010: _3035::BY,
+        _020: Some("A+B's?".to_string()),
+        _030: Some(C080 {
+            _010: "x:y".to_string(),
+            _020: None,
+            _030: Some("z".to_string()),
+        }),
+        _040: None,
+    };
+    assert_eq!(nad.to_string(), "NAD+BY+A?+B?'s??+x?:y::z");
+}
+
+#[test]
+fn parse_released() {
+    let s = "NAD+BY+A?+B?'s??+x?:y::z'";
+    let (rest, nad) = NAD::parse(s).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(nad._020.as_deref(), Some("A+B's?"));
+    assert_eq!(nad._030.as_ref().unwrap()._010, "x:y");
+    assert_eq!(nad.to_string() + "'", s);
+    let nad2: NAD = s.parse().unwrap();
+    assert_eq!(nad, nad2);
+    let c: C080 = "x?:y::z".parse().unwrap();
+    assert_eq!(c._010, "x:y");
+}
+
+#[test]
+fn una_separators() {
+    let s = "UNA|*.# \"\nBGM*a|b*c#*d\"\nNAD*SU\"";
+    let (rest, m) = Interchange::parse(s).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(m.bgm._010.as_deref(), Some("a|b"));
+    assert_eq!(m.bgm._020.as_deref(), Some("c*d"));
+    assert_eq!(m.to_string(), "UNA|*.# \"\nBGM*a#|b*c#*d\"\nNAD*SU\"");
+}
+
+#[test]
+fn repetition() {
+    let s = "RPT+a+b*c?*d*e+x:y*z+w'";
+    let (_, r) = RPT::parse(s).unwrap();
+    assert_eq!(r._020, vec!["b", "c*d", "e"]);
+    assert_eq!(r._030.len(), 2);
+    assert_eq!(r._030[1]._010, "z");
+    assert_eq!(r.to_string() + "'", s);
+    let r2: RPT = s.parse().unwrap();
+    assert_eq!(r, r2);
+    assert!(RPT::parse("RPT+a+b*c*d*e'").is_err());
+    assert!("RPT+a+b*c*d*e'".parse::<RPT>().is_err());
+    let (_, r) = RPT::parse("RPT+a'").unwrap();
+    assert!(r._020.is_empty());
+    assert_eq!(r.to_string(), "RPT+a");
+}
+
+#[test]
+fn errors_instead_of_panics() {
+    // unknown code
+    let e = NAD::parse("NAD+XX'").unwrap_err();
+    match e {
+        nom::Err::Failure(e) => assert_eq!(e.found, "XX"),
+        _ => panic!("{e:?}"),
+    }
+    // missing mandatory
+    assert!(matches!(DTM::parse("DTM'"), Err(nom::Err::Failure(_))));
+    // wrong tag backtracks
+    assert!(matches!(DTM::parse("NAD+BY'"), Err(nom::Err::Error(_))));
+    // message with bad segment fails instead of panicking
+    assert!(MSG::parse("BGM+a'\nNAD+XX'").is_err());
+    let e = "NAD+XX'".parse::<NAD>().unwrap_err();
+    assert_eq!(e.path(), "NAD/3035");
+    assert_eq!(e.element, Some(1));
+    assert_eq!(e.offset, Some(4));
+    let n = "NAD+BY++x'".parse::<NAD>().unwrap();
+    assert_eq!(n._030.unwrap()._010, "x");
+}
+
+#[test]
+fn from_str_rejects_missing_mandatory() {
+    for s in ["NAD", "NAD+", "NAD++x"] {
+        let e = s.parse::<NAD>().unwrap_err();
+        assert_eq!(e.path(), "NAD/3035");
+        assert_eq!(e.expected, "mandatory value");
+        assert_eq!(e.element, Some(1));
+        assert!(matches!(
+            NAD::parse(&format!("{s}'")),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+    let e = ":a".parse::<C080>().unwrap_err();
+    assert_eq!(e.path(), "010");
+    assert_eq!(e.component, Some(1));
+    assert_eq!(e.offset, Some(0));
+    assert_eq!("x".parse::<C080>().unwrap()._010, "x");
+    let e = "DTM".parse::<NAD>().unwrap_err();
+    assert_eq!(e.expected, "NAD");
+}
+
+#[test]
+fn rich_errors() {
+    let s = "BGM+a'\nNAD+BY'\nDTM+1'\nNAD+XX+x'";
+    let e = match MSG::parse(s).unwrap_err() {
+        nom::Err::Failure(e) => e,
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(e.path(), "MSG/SG1/NAD/3035");
+    assert_eq!(e.element, Some(1));
+    assert_eq!(e.segment, Some(4));
+    assert_eq!(e.found, "XX");
+    assert_eq!(&s[e.offset.unwrap()..], "XX+x'");
+    let e = "NAD+BY++a:b:c:d".parse::<NAD>().unwrap_err();
+    assert_eq!(e.path(), "NAD/C080");
+    assert_eq!(e.offset, Some(14));
+    let e = match DTM::parse("DTM+'").unwrap_err() {
+        nom::Err::Failure(e) => e,
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(e.path(), "DTM/010");
+    assert_eq!(e.offset, Some(4));
+}
+
+#[test]
+fn token_input() {
+    let s = "BGM+a'\nNAD+BY'\nDTM+1'\nDTM+2'\nNAD+SU'";
+    let seps = util::Separators::default();
+    let ctx = util::ParseContext::new(seps.clone());
+    let (tokens, rest) = util::tokenize(s, &ctx);
+    assert_eq!(rest, "");
+    let (left, msg) = MSG::parse_tokens(&tokens, &ctx, None).unwrap();
+    assert!(left.is_empty());
+    assert_eq!(msg.sg1.len(), 2);
+    assert_eq!(msg.sg1[0].dtm.len(), 2);
+    let (left, sg) = MsgSg1::parse_tokens(&tokens[1..], &ctx, None).unwrap();
+    assert_eq!(left.len(), 1);
+    assert_eq!(sg.dtm.len(), 2);
+    let (rest, sg) = MsgSg1::parse_with("NAD+BY'\nDTM+1'\nBGM+x'", &ctx).unwrap();
+    assert_eq!(rest, "BGM+x'");
+    assert_eq!(sg.dtm.len(), 1);
+    let (rest, _) = MsgSg1::parse_with("NAD+BY'DTM+1", &ctx).unwrap();
+    assert_eq!(rest, "DTM+1");
+}
@@ -0,0 +1,108 @@
+mod types;
+mod util;
+
+use edifact_types_macros::{DisplayEdifactSg, Edifact, ParseSg};
+use std::borrow::Cow;
+use types::_3035;
+use util::{tokenize, Parser, Separators};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C080<'a> {
+    pub _010: Cow<'a, str>,
+    pub _020: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD<'a> {
+    #[edifact(code)]
+    pub _010: _3035,
+    pub _020: Option<C080<'a>>,
+    pub _030: Vec<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct FTX<'a> {
+    pub _010: &'a str,
+    pub _020: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(group, no_into_owned)]
+pub struct Sg<'a> {
+    pub nad: NAD<'a>,
+    pub ftx: Vec<FTX<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct OwnedSg<'a> {
+    pub nad: NAD<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, ParseSg, DisplayEdifactSg)]
+pub enum Choice<'a> {
+    #[edifact(qualifier = "BY")]
+    Buyer(NAD<'a>),
+    Other(OwnedSg<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(message, no_into_owned)]
+pub struct Msg<'a> {
+    pub sg: Vec<Sg<'a>>,
+}
+
+#[test]
+fn borrows_from_input() {
+    let s = "NAD+BY+Bob:Sm?'ith+a*b'\nFTX+x+y'";
+    let (rest, msg) = Msg::parse(s).unwrap();
+    assert_eq!(rest, "");
+    let nad = &msg.sg[0].nad;
+    let c080 = nad._020.as_ref().unwrap();
+    assert!(matches!(c080._010, Cow::Borrowed("Bob")));
+    assert!(matches!(&c080._020, Some(Cow::Owned(x)) if x == "Sm'ith"));
+    assert_eq!(nad._030, vec!["a", "b"]);
+    assert_eq!(msg.sg[0].ftx[0]._010, "x");
+    assert_eq!(msg.to_string(), s);
+    let seps = Separators::default();
+    let ctx = util::ParseContext::new(seps.clone());
+    let (tokens, _) = tokenize(s, &ctx);
+    let (rest, sg) = Sg::parse_tokens(&tokens, &ctx, None).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(sg, msg.sg[0]);
+}
+
+#[test]
+fn into_owned() {
+    let owned: NAD<'static> = {
+        let s = String::from("NAD+BY+Bob:Sm?'ith'");
+        let (_, nad) = NAD::parse(&s).unwrap();
+        nad.into_owned()
+    };
+    assert_eq!(owned.to_string(), "NAD+BY+Bob:Sm?'ith");
+    let choice: Choice<'static> = {
+        let s = String::from("NAD+SU'");
+        Choice::parse(&s).unwrap().1.into_owned()
+    };
+    assert!(matches!(choice, Choice::Other(_)));
+}
+
+#[test]
+fn borrowed_text_is_escaped() {
+    let ftx = FTX {
+        _010: "x",
+        _020: Some("a+b'c"),
+    };
+    assert_eq!(ftx.to_string(), "FTX+x+a?+b?'c");
+    // the slice cannot drop the release characters
+    let e = FTX::parse("FTX+x+a?+b'").unwrap_err();
+    let nom::Err::Failure(e) = e else {
+        panic!("{e:?}")
+    };
+    assert_eq!(e.path(), "FTX/020");
+    assert_eq!(e.expected, "text without release character");
+    assert_eq!(e.found, "a?+b");
+}
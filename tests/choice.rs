@@ -0,0 +1,66 @@
+mod types;
+mod util;
+
+use edifact_types_macros::{DisplayEdifactSg, Edifact, ParseSg};
+use types::{_3035, NAD};
+use util::Parser;
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct LOC {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct SgLoc {
+    pub loc: LOC,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, PartialEq, DisplayEdifactSg, ParseSg)]
+pub enum Party {
+    #[edifact(qualifier = "BY")]
+    Buyer(NAD),
+    Place(SgLoc),
+    Other(NAD),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub dtm: DTM,
+    pub parties: Vec<Party>,
+    pub tail: Option<DTM>,
+}
+
+#[test]
+fn choice() {
+    let s = "DTM+1'\nNAD+SU'\nLOC+a'\nDTM+2'\nNAD+BY'\nDTM+3'";
+    let msg: Msg = s.parse().unwrap();
+    assert!(matches!(msg.parties[0], Party::Other(_)));
+    assert!(matches!(&msg.parties[1], Party::Place(sg) if sg.dtm.len() == 1));
+    assert!(matches!(
+        msg.parties[2],
+        Party::Buyer(NAD {
+            _010: _3035::BY,
+            ..
+        })
+    ));
+    assert!(msg.tail.is_some());
+    assert_eq!(msg.to_string(), s);
+    let e = match Party::parse("DTM+1'") {
+        Err(nom::Err::Error(e)) => e,
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(e.expected, "one of Buyer, Place, Other");
+    assert_eq!(e.found, "DTM");
+    let e = "DTM+1'\nLOC+'".parse::<Msg>().unwrap_err();
+    assert_eq!(e.path(), "MSG/PARTIES/PLACE/LOC/010");
+}
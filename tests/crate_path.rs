@@ -0,0 +1,48 @@
+mod types;
+mod util;
+
+use edifact_types_macros::*;
+use util::Parser;
+
+/// The runtime under another path, as in a crate depending on edifact-types.
+mod host {
+    pub(crate) use crate::util;
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, DisplayInnerSegment, ParseElement, ParseInnerSegment,
+)]
+#[edifact(crate = "crate::host")]
+pub struct C506 {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseSegment, ParseOuterSegment,
+)]
+#[edifact(crate = "crate::host")]
+pub struct RFF {
+    #[edifact(composite)]
+    pub _010: C506,
+    #[edifact(code)]
+    pub _020: Option<types::_3035>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayEdifactSg, ParseSg)]
+#[edifact(crate = "crate::host")]
+pub struct Sg {
+    pub rff: RFF,
+    pub nad: Vec<types::NAD>,
+}
+
+#[test]
+fn derive_outside_host() {
+    let s = "RFF+ON:4?+1+SU'\nNAD+BY'";
+    let (rest, sg) = Sg::parse(s).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(sg.rff._010._020.as_deref(), Some("4+1"));
+    assert_eq!(sg.to_string(), "RFF+ON:4?+1+SU'\nNAD+BY");
+    let e = "RFF+ON:1+XX".parse::<RFF>().unwrap_err();
+    assert_eq!(e.path(), "RFF/3035");
+}
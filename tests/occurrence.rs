@@ -0,0 +1,76 @@
+mod util;
+
+use edifact_types_macros::Edifact;
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct LOC {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub loc: Option<LOC>,
+    #[edifact(min = 1, max = 2)]
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg2 {
+    pub dtm: Option<DTM>,
+    #[edifact(trigger)]
+    pub loc: LOC,
+    pub bgm: BGM,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub bgm: BGM,
+    #[edifact(max = 2)]
+    pub sg1: Vec<Sg1>,
+    pub sg2: Option<Sg2>,
+}
+
+#[test]
+fn occurrences() {
+    let msg: Msg = "BGM+1'LOC+a'DTM+1'DTM+2'LOC+b'DTM+3'".parse().unwrap();
+    assert_eq!(msg.sg1.len(), 2);
+    // optional trigger is still required for the group to exist
+    let msg: Msg = "BGM+1'".parse().unwrap();
+    assert!(msg.sg1.is_empty());
+    let e = "BGM+1'LOC+a'BGM+2'".parse::<Msg>().unwrap_err();
+    assert_eq!(e.path(), "MSG/SG1/DTM");
+    assert_eq!(e.expected, "at least 1 occurrences");
+    assert_eq!(e.found, "BGM");
+    let e = "BGM+1'LOC+a'DTM+1'DTM+2'DTM+3'".parse::<Msg>().unwrap_err();
+    assert_eq!(e.path(), "MSG/SG1/DTM");
+    assert_eq!(e.expected, "at most 2 occurrences");
+    let e = "BGM+1'LOC+a'DTM+1'LOC+b'DTM+3'LOC+c'DTM+1'"
+        .parse::<Msg>()
+        .unwrap_err();
+    assert_eq!(e.path(), "MSG/SG1");
+    assert_eq!(e.expected, "at most 2 occurrences");
+    assert_eq!(e.found, "LOC");
+    // explicit trigger, the segment before it does not commit the group
+    let e = "BGM+1'DTM+1'LOC+b'DTM+9'".parse::<Msg>().unwrap_err();
+    assert_eq!(e.path(), "MSG/SG2/BGM");
+    let e = "BGM+1'DTM+1'BGM+2'".parse::<Msg>().unwrap_err();
+    assert_eq!(e.expected, "end of input");
+    let msg: Msg = "BGM+1'DTM+8'LOC+b'BGM+2'".parse().unwrap();
+    assert_eq!(msg.sg2.unwrap().dtm.unwrap()._010, "8");
+}
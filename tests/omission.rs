@@ -0,0 +1,127 @@
+mod util;
+
+use edifact_types_macros::Edifact;
+use util::Parser;
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C506 {
+    pub _010: Option<String>,
+    pub _020: Option<String>,
+    pub _030: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct RFF {
+    pub _010: Option<C506>,
+    pub _020: Option<String>,
+    pub _030: Vec<String>,
+    pub _040: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub rff: Option<RFF>,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub dtm: Option<DTM>,
+    pub sg1: Vec<Sg1>,
+    pub tail: Option<DTM>,
+}
+
+fn c506(a: Option<&str>, b: Option<&str>, c: Option<&str>) -> C506 {
+    C506 {
+        _010: a.map(String::from),
+        _020: b.map(String::from),
+        _030: c.map(String::from),
+    }
+}
+
+#[test]
+fn components() {
+    assert_eq!(c506(Some("a"), None, None).to_string(), "a");
+    assert_eq!(c506(None, None, Some("c")).to_string(), "::c");
+    assert_eq!(c506(Some("a"), None, Some("c")).to_string(), "a::c");
+    assert_eq!(c506(None, None, None).to_string(), "");
+}
+
+#[test]
+fn elements() {
+    let rff = |c: Option<C506>, b: Option<&str>, rep: &[&str], d: Option<&str>| RFF {
+        _010: c,
+        _020: b.map(String::from),
+        _030: rep.iter().map(|s| s.to_string()).collect(),
+        _040: d.map(String::from),
+    };
+    let reference = [
+        (
+            rff(Some(c506(Some("ON"), None, None)), None, &[], None),
+            "RFF+ON",
+        ),
+        (
+            rff(Some(c506(Some("ON"), None, None)), Some("x"), &[], None),
+            "RFF+ON+x",
+        ),
+        // a composite of empty components is an empty element
+        (
+            rff(Some(c506(None, None, None)), None, &[], Some("d")),
+            "RFF++++d",
+        ),
+        (rff(None, None, &["a", "", "b"], None), "RFF+++a**b"),
+        (rff(None, None, &["a", ""], None), "RFF+++a"),
+        (
+            rff(Some(c506(Some("ON"), Some(""), None)), None, &[""], None),
+            "RFF+ON",
+        ),
+        (rff(None, None, &[], None), ""),
+    ];
+    for (value, text) in reference {
+        assert_eq!(value.to_string(), text);
+    }
+    // omitted elements and components are `None`, not empty text
+    let parsed: RFF = "RFF+:b++a*b+'".parse().unwrap();
+    assert_eq!(parsed._010, Some(c506(None, Some("b"), None)));
+    assert_eq!(parsed._020, None);
+    assert_eq!(parsed._040, None);
+    assert_eq!(parsed.to_string(), "RFF+:b++a*b");
+}
+
+#[test]
+fn segments_and_groups() {
+    let msg = Msg {
+        dtm: Some(DTM::default()),
+        sg1: vec![
+            Sg1::default(),
+            Sg1 {
+                rff: None,
+                dtm: vec![
+                    DTM::default(),
+                    DTM {
+                        _010: Some("1".into()),
+                    },
+                ],
+            },
+        ],
+        tail: Some(DTM {
+            _010: Some("2".into()),
+        }),
+    };
+    assert_eq!(msg.to_string(), "DTM+1'\nDTM+2'");
+    assert_eq!(Msg::default().to_string(), "");
+    let reference = "RFF+ON:4711'\nDTM+137'\nRFF+++a'\nDTM+9'";
+    let (rest, msg) = Msg::parse(reference).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(msg.to_string(), reference);
+}
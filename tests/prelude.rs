@@ -0,0 +1,91 @@
+mod types;
+mod util;
+
+/// Derives next to items shadowing the prelude, as in a crate with its own `Result`.
+mod shadowed {
+    #![allow(dead_code, unused_imports, clippy::upper_case_acronyms)]
+    use crate::types::{_3035, C080};
+    use edifact_types_macros::*;
+
+    pub type Result<T> = core::result::Result<T, ()>;
+    pub struct Vec;
+    pub enum Shadow {
+        Some,
+        None,
+        Ok,
+        Err,
+    }
+    pub use Shadow::*;
+
+    #[derive(Debug, Clone, Default, PartialEq, Edifact)]
+    #[edifact(composite)]
+    pub struct C082 {
+        pub _010: String,
+        pub _020: Option<String>,
+        pub _030: std::vec::Vec<String>,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Edifact)]
+    #[edifact(segment, tag = "NAD", qualifier = "BY")]
+    pub struct NadBuyer {
+        #[edifact(code)]
+        pub _010: _3035,
+        pub _020: Option<C082>,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Edifact)]
+    #[edifact(segment)]
+    pub struct DTM {
+        pub _010: String,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Edifact)]
+    #[edifact(group)]
+    pub struct Sg1 {
+        pub nad: NadBuyer,
+        pub dtm: std::vec::Vec<DTM>,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Edifact)]
+    #[edifact(message)]
+    pub struct Msg {
+        pub dtm: DTM,
+        pub sg1: Option<Sg1>,
+    }
+
+    #[derive(
+        Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseSegment, ParseOuterSegment,
+    )]
+    pub struct NAD {
+        #[edifact(code)]
+        pub _010: _3035,
+        #[edifact(composite)]
+        pub _020: Option<C080>,
+        pub _030: std::vec::Vec<String>,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, DisplayEdifactSg, ParseSg)]
+    pub struct Sg2 {
+        pub nad: NAD,
+        pub dtm: Option<DTM>,
+    }
+}
+
+use shadowed::{Msg, Sg2};
+use util::Parser;
+
+#[test]
+fn expansions_ignore_shadowed_prelude() {
+    let s = "DTM+1'\nNAD+BY+a:b:c'\nDTM+2'";
+    let msg: Msg = s.parse().unwrap();
+    assert_eq!(
+        msg.sg1.as_ref().unwrap().nad._020.as_ref().unwrap()._030,
+        ["c"]
+    );
+    assert_eq!(msg.to_string(), s);
+    let s = "NAD+SU+x+y'\nDTM+3'";
+    let (rest, sg) = Sg2::parse(s).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(sg.nad._030, ["y"]);
+    assert_eq!(sg.to_string(), s.trim_end_matches('\''));
+}
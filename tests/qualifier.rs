@@ -0,0 +1,125 @@
+mod types;
+mod util;
+
+use edifact_types_macros::Edifact;
+use types::_3035;
+use util::{tokenize, ParseContext, Separators};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD {
+    #[edifact(code)]
+    pub _010: _3035,
+    pub _020: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment, tag = "NAD", qualifier = "SU")]
+pub struct NadSeller {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg {
+    pub dtm: DTM,
+    #[edifact(qualifier = "BY")]
+    pub buyer: Option<NAD>,
+    pub seller: Option<NadSeller>,
+    #[edifact(qualifier = "DP")]
+    pub delivery: Vec<NAD>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment, tag = "NAD", qualifier = "BY")]
+pub struct NadBuyer {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+/// Plain fields are parsed without looking ahead first.
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg2 {
+    pub buyer: NadBuyer,
+    pub dtm: DTM,
+    pub seller: NadSeller,
+}
+
+#[test]
+fn qualifier_picks_field() {
+    let sg: Sg = "DTM+1'NAD+SU+s'".parse().unwrap();
+    assert_eq!(sg.buyer, None);
+    assert_eq!(sg.seller.as_ref().unwrap()._020.as_deref(), Some("s"));
+    let sg: Sg = "DTM+1'NAD+BY+b'NAD+SU'".parse().unwrap();
+    assert_eq!(sg.buyer.unwrap()._020.as_deref(), Some("b"));
+    assert!(sg.seller.is_some());
+    let e = "DTM+1'NAD+XX'".parse::<Sg>().unwrap_err();
+    assert_eq!(e.path(), "SG");
+    assert_eq!(e.expected, "end of input");
+    let e = "NAD+BY'".parse::<NadSeller>().unwrap_err();
+    assert_eq!(e.expected, "qualifier SU");
+    assert_eq!(e.found, "BY");
+}
+
+#[test]
+fn plain_qualified_field() {
+    let ctx = ParseContext::new(Separators::default());
+    let (tokens, _) = tokenize("NAD+SU'", &ctx);
+    assert!(Sg2::parse_tokens(&tokens, &ctx, None).is_err());
+    let sg: Sg2 = "NAD+BY+b'DTM+1'NAD+SU+s'".parse().unwrap();
+    assert_eq!(sg.buyer._020.as_deref(), Some("b"));
+    assert_eq!(sg.seller._020.as_deref(), Some("s"));
+    let e = "NAD+BY'DTM+1'NAD+BY'".parse::<Sg2>().unwrap_err();
+    assert_eq!(e.path(), "SG2/SELLER");
+    assert_eq!(e.expected, "qualifier SU");
+    assert_eq!(e.found, "BY");
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct PartySg {
+    pub nad: NAD,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(group)]
+pub enum Party {
+    #[edifact(qualifier = "BY")]
+    Buyer(PartySg),
+    #[edifact(qualifier = "SU")]
+    Seller(PartySg),
+}
+
+/// Groups of the same type told apart by the qualifier of their trigger.
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Parties {
+    pub dtm: DTM,
+    #[edifact(qualifier = "BY")]
+    pub buyer: Option<PartySg>,
+    #[edifact(qualifier = "SU")]
+    pub seller: Option<PartySg>,
+    pub other: Vec<Party>,
+}
+
+#[test]
+fn qualifier_picks_group() {
+    let msg: Parties = "DTM+1'NAD+SU'DTM+1'".parse().unwrap();
+    assert_eq!(msg.buyer, None);
+    assert_eq!(msg.seller.unwrap().dtm.len(), 1);
+    let msg: Parties = "DTM+1'NAD+BY+b'NAD+SU+s'NAD+SU'NAD+BY'".parse().unwrap();
+    assert_eq!(msg.buyer.unwrap().nad._020.as_deref(), Some("b"));
+    assert_eq!(msg.seller.unwrap().nad._020.as_deref(), Some("s"));
+    assert!(matches!(msg.other[..], [Party::Seller(_), Party::Buyer(_)]));
+    let e = "DTM+1'NAD+DP'".parse::<Parties>().unwrap_err();
+    assert_eq!(e.expected, "end of input");
+}
@@ -0,0 +1,192 @@
+mod util;
+
+use edifact_types_macros::Edifact;
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+    pub _020: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct LOC {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub loc: LOC,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub bgm: BGM,
+    pub dtm: Option<DTM>,
+    pub sg1: Vec<Sg1>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct UNH {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct RFF {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment, tag = "DTM")]
+pub struct Date {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct RffSg {
+    pub rff: RFF,
+    #[edifact(max = 2)]
+    pub dtm: Vec<Date>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct NadSg {
+    pub nad: NAD,
+    pub dtm: Vec<Date>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Limited {
+    pub unh: UNH,
+    pub bgm: BGM,
+    pub sg1: Vec<RffSg>,
+    pub sg2: Option<NadSg>,
+}
+
+#[test]
+fn recovers() {
+    let s = "BGM+1'DTM+x'LOC+a'DTM+1+2'DTM+y'DTM+3+4'LOC+b'";
+    let (msg, diagnostics) = Msg::parse_recovering(s);
+    let msg = msg.unwrap();
+    assert_eq!(msg.dtm, None);
+    assert_eq!(msg.sg1.len(), 2);
+    assert_eq!(msg.sg1[0].dtm.len(), 2);
+    assert_eq!(msg.sg1[0].dtm[1]._010, "3");
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].path(), "MSG/DTM/020");
+    assert_eq!(diagnostics[0].segment, Some(2));
+    assert_eq!(diagnostics[1].path(), "MSG/SG1/DTM/020");
+    assert_eq!(diagnostics[1].segment, Some(5));
+    // without recovery the first problem ends parsing
+    assert!(s.parse::<Msg>().is_err());
+}
+
+#[test]
+fn skips_to_mandatory() {
+    let (msg, diagnostics) = Msg::parse_recovering("BGM'BGM+1'LOC+a'xyz");
+    let msg = msg.unwrap();
+    assert_eq!(msg.bgm._010, "1");
+    assert_eq!(msg.sg1.len(), 1);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].path(), "MSG/BGM/010");
+    assert_eq!(diagnostics[1].expected, "end of input");
+    let (msg, diagnostics) = Msg::parse_recovering("LOC+a'");
+    assert!(msg.is_none());
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn keeps_group_over_max() {
+    let s = "UNH+1'BGM+x'RFF+a'DTM+1'DTM+2'DTM+3'RFF+b'NAD+BY'";
+    let (msg, diagnostics) = Limited::parse_recovering(s);
+    let msg = msg.unwrap();
+    assert_eq!(msg.sg1.len(), 2);
+    assert_eq!(msg.sg1[0].dtm.len(), 2);
+    assert_eq!(msg.sg1[1].rff._010, "b");
+    assert_eq!(msg.sg2.unwrap().nad._010, "BY");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path(), "LIMITED/SG1/DTM");
+    assert_eq!(diagnostics[0].expected, "at most 2 occurrences");
+    assert_eq!(diagnostics[0].segment, Some(6));
+    assert!(s.parse::<Limited>().is_err());
+}
+
+#[test]
+fn skips_broken_segments() {
+    // the broken group is skipped with its dates, the next group starts again
+    let s = "BGM+1'LOC'DTM+1+2'DTM+3+4'LOC+b'DTM+5+6'";
+    let (msg, diagnostics) = Msg::parse_recovering(s);
+    let msg = msg.unwrap();
+    assert_eq!(msg.sg1.len(), 1);
+    assert_eq!(msg.sg1[0].loc._010, "b");
+    assert_eq!(msg.sg1[0].dtm.len(), 1);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path(), "MSG/SG1/LOC/010");
+    // each broken date is reported on its own, the group is kept
+    let s = "BGM+1'LOC+a'DTM+x'DTM+y'DTM+1+2'";
+    let (msg, diagnostics) = Msg::parse_recovering(s);
+    let msg = msg.unwrap();
+    assert_eq!(msg.sg1[0].dtm.len(), 1);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[1].segment, Some(4));
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct UNT {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Framed {
+    pub unh: UNH,
+    pub bgm: BGM,
+    pub dtm: Vec<Date>,
+    pub unt: UNT,
+}
+
+#[test]
+fn skips_stray_segments() {
+    // a segment nobody knows in front of a mandatory one
+    let (msg, diagnostics) = Framed::parse_recovering("UNH+1'BGM+1'DTM+1'XXX+1'UNT+1'");
+    let msg = msg.unwrap();
+    assert_eq!(msg.dtm.len(), 1);
+    assert_eq!(msg.unt._010, "1");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path(), "FRAMED/UNT");
+    assert_eq!(diagnostics[0].segment, Some(4));
+    // a segment placed too early is dropped, the mandatory one behind it is taken
+    let (msg, diagnostics) = Framed::parse_recovering("UNH+1'DTM+1'BGM+1'UNT+1'");
+    let msg = msg.unwrap();
+    assert_eq!(msg.bgm._010, "1");
+    assert!(msg.dtm.is_empty());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path(), "FRAMED/BGM");
+    assert_eq!(diagnostics[0].segment, Some(2));
+    // a mandatory segment that never comes still fails the message
+    let (msg, _) = Framed::parse_recovering("UNH+1'BGM+1'XXX+1'");
+    assert!(msg.is_none());
+    assert!("UNH+1'DTM+1'BGM+1'UNT+1'".parse::<Framed>().is_err());
+}
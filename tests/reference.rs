@@ -0,0 +1,263 @@
+mod types;
+mod util;
+
+use edifact_types_macros::Edifact;
+use types::UNA;
+use util::Parser;
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct S009 {
+    pub _010: String,
+    pub _020: String,
+    pub _030: String,
+    pub _040: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct UNH {
+    pub _010: String,
+    pub _020: S009,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C002 {
+    pub _010: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C106 {
+    pub _010: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: Option<C002>,
+    pub _020: Option<C106>,
+    pub _030: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C507 {
+    pub _010: String,
+    pub _020: Option<String>,
+    pub _030: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: C507,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C108 {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct FTX {
+    pub _010: String,
+    pub _020: Option<String>,
+    pub _030: Option<String>,
+    pub _040: Option<C108>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C082 {
+    pub _010: String,
+    pub _020: Option<String>,
+    pub _030: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C080 {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD {
+    pub _010: String,
+    pub _020: Option<C082>,
+    pub _030: Option<String>,
+    pub _040: Option<C080>,
+    pub _050: Option<String>,
+    pub _060: Option<String>,
+    pub _070: Option<String>,
+    pub _080: Option<String>,
+    pub _090: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct UNT {
+    pub _010: String,
+    pub _020: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct IftminSg11 {
+    pub nad: NAD,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct IFTMIN {
+    #[edifact(una)]
+    pub una: Option<UNA>,
+    pub unh: UNH,
+    pub bgm: BGM,
+    pub dtm: Vec<DTM>,
+    pub ftx: Vec<FTX>,
+    pub sg11: Vec<IftminSg11>,
+    pub unt: UNT,
+}
+
+/// Escaped values, empty interior elements and components, dropped trailing ones.
+const REFERENCE: &str = "UNH+1+IFTMIN:D:04A:UN'
+BGM+610+REF?+1?:A+9'
+DTM+137:20240101:102'
+FTX+AAI+++Line one?: with colon:Line two'
+NAD+CZ+4012345000009::9++Shipper Ltd?'s+Main Street 1+Hamburg++20095'
+DTM+200:20240102'
+NAD+CN+++Consignee+Harbour Road??+Rotterdam'
+UNT+7+1'";
+
+#[test]
+fn reference_message_round_trips() {
+    let msg: IFTMIN = REFERENCE.parse().unwrap();
+    assert_eq!(
+        msg.bgm._020.as_ref().unwrap()._010.as_deref(),
+        Some("REF+1:A")
+    );
+    assert_eq!(
+        msg.ftx[0]._040.as_ref().unwrap()._010,
+        "Line one: with colon"
+    );
+    assert_eq!(msg.sg11.len(), 2);
+    assert_eq!(msg.sg11[0].nad._040.as_ref().unwrap()._010, "Shipper Ltd's");
+    assert_eq!(msg.sg11[0].nad._030, None);
+    assert_eq!(msg.sg11[1].nad._050.as_deref(), Some("Harbour Road?"));
+    assert_eq!(msg.to_string(), REFERENCE);
+}
+
+#[test]
+fn built_message_prints_reference() {
+    let msg = IFTMIN {
+        una: None,
+        unh: UNH {
+            _010: "1".into(),
+            _020: S009 {
+                _010: "IFTMIN".into(),
+                _020: "D".into(),
+                _030: "04A".into(),
+                _040: "UN".into(),
+            },
+        },
+        bgm: BGM {
+            _010: Some(C002 {
+                _010: Some("610".into()),
+            }),
+            _020: Some(C106 {
+                _010: Some("REF+1:A".into()),
+            }),
+            _030: Some("9".into()),
+        },
+        dtm: vec![DTM {
+            _010: C507 {
+                _010: "137".into(),
+                _020: Some("20240101".into()),
+                _030: Some("102".into()),
+            },
+        }],
+        ftx: vec![FTX {
+            _010: "AAI".into(),
+            _040: Some(C108 {
+                _010: "Line one: with colon".into(),
+                _020: Some("Line two".into()),
+            }),
+            ..Default::default()
+        }],
+        sg11: vec![
+            IftminSg11 {
+                nad: NAD {
+                    _010: "CZ".into(),
+                    _020: Some(C082 {
+                        _010: "4012345000009".into(),
+                        _030: Some("9".into()),
+                        ..Default::default()
+                    }),
+                    _040: Some(C080 {
+                        _010: "Shipper Ltd's".into(),
+                        _020: None,
+                    }),
+                    _050: Some("Main Street 1".into()),
+                    _060: Some("Hamburg".into()),
+                    _080: Some("20095".into()),
+                    ..Default::default()
+                },
+                dtm: vec![DTM {
+                    _010: C507 {
+                        _010: "200".into(),
+                        _020: Some("20240102".into()),
+                        _030: None,
+                    },
+                }],
+            },
+            IftminSg11 {
+                nad: NAD {
+                    _010: "CN".into(),
+                    _040: Some(C080 {
+                        _010: "Consignee".into(),
+                        _020: None,
+                    }),
+                    _050: Some("Harbour Road?".into()),
+                    _060: Some("Rotterdam".into()),
+                    // empty values at the end are left out
+                    _070: Some(String::new()),
+                    _090: None,
+                    ..Default::default()
+                },
+                dtm: vec![],
+            },
+        ],
+        unt: UNT {
+            _010: "7".into(),
+            _020: "1".into(),
+        },
+    };
+    assert_eq!(msg.to_string(), REFERENCE);
+}
+
+#[test]
+fn una_separators_round_trip() {
+    let s = "UNA|=.# ~
+UNH=1=IFTMIN|D|04A|UN~
+BGM==REF#=1#|A~
+FTX=AAI===a#~b|c~
+UNT=4=1~";
+    let (rest, msg) = IFTMIN::parse(s).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(msg.una, Some(UNA("UNA|=.# ~".into())));
+    assert_eq!(msg.bgm._010, None);
+    assert_eq!(
+        msg.bgm._020.as_ref().unwrap()._010.as_deref(),
+        Some("REF=1|A")
+    );
+    assert_eq!(msg.ftx[0]._040.as_ref().unwrap()._010, "a~b");
+    assert_eq!(msg.to_string(), s);
+}
@@ -0,0 +1,162 @@
+mod util;
+
+use edifact_types_macros::Edifact;
+use util::{ParseContext, Separators, Span};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct RFF {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct FTX {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct CTA {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct UNH {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg2 {
+    pub cta: CTA,
+    pub dtm: Option<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub nad: NAD,
+    pub rff: Option<RFF>,
+    pub sg2: Vec<Sg2>,
+    #[edifact(span)]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub bgm: BGM,
+    pub ftx: Vec<FTX>,
+    pub sg1: Vec<Sg1>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Ambiguous {
+    pub bgm: BGM,
+    pub rff: Vec<RFF>,
+    pub sg1: Vec<Sg1>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct RffSg {
+    pub rff: RFF,
+    pub dtm: Option<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct DtmSg {
+    pub dtm: DTM,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Shared {
+    pub unh: UNH,
+    pub bgm: BGM,
+    pub sg1: Vec<RffSg>,
+    pub sg2: Vec<DtmSg>,
+}
+
+#[test]
+fn reorders() {
+    let s = "FTX+1'BGM+1'NAD+a'CTA+x'RFF+2'NAD+b'RFF+3'";
+    assert!(s.parse::<Msg>().is_err());
+    let ctx = ParseContext::new(Separators::default()).reorder();
+    let (rest, msg) = Msg::parse_with(s, &ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(msg.bgm._010, "1");
+    assert_eq!(msg.ftx.len(), 1);
+    assert_eq!(msg.sg1.len(), 2);
+    assert_eq!(msg.sg1[0].rff.as_ref().unwrap()._010, "2");
+    assert_eq!(msg.sg1[0].sg2.len(), 1);
+    assert_eq!(msg.sg1[0].span, Span::new(12, 30, 3));
+    assert_eq!(msg.sg1[1].rff.as_ref().unwrap()._010, "3");
+    let warnings = ctx.take_warnings();
+    assert_eq!(warnings.len(), 2);
+    // the groups inside report first
+    assert_eq!(
+        warnings[1].to_string(),
+        r#"MSG: expected BGM before FTX, found """#
+    );
+    let w = warnings[0].clone().locate(s, &ctx);
+    assert_eq!(w.path(), "SG1");
+    assert_eq!(w.expected, "RFF before SG2");
+    assert_eq!(w.segment, Some(5));
+    // in order nothing is reported
+    let (_, ordered) = Msg::parse_with(&msg.to_string(), &ctx).unwrap();
+    assert_eq!(ordered.sg1.len(), 2);
+    assert!(ctx.take_warnings().is_empty());
+}
+
+#[test]
+fn ambiguous_left_alone() {
+    // RFF is known to the message and the group
+    let ctx = ParseContext::new(Separators::default()).reorder();
+    assert!(Ambiguous::parse_with("RFF+1'BGM+1'", &ctx).is_err());
+    // DTM is known to Sg2 only, the repeated trigger starts a new group
+    let ctx = ParseContext::new(Separators::default()).reorder();
+    let (_, msg) = Msg::parse_with("BGM+1'NAD+a'CTA+x'DTM+1'CTA+y'NAD+b'", &ctx).unwrap();
+    assert_eq!(msg.sg1.len(), 2);
+    assert_eq!(msg.sg1[0].sg2.len(), 2);
+    assert!(ctx.take_warnings().is_empty());
+}
+
+#[test]
+fn reorders_up_to_ambiguous() {
+    // DTM is known to both groups, the segments before it are sorted
+    let s = "BGM+1'UNH+1'RFF+a'DTM+1'DTM+2'";
+    assert!(s.parse::<Shared>().is_err());
+    let ctx = ParseContext::new(Separators::default()).reorder();
+    let (rest, msg) = Shared::parse_with(s, &ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(msg.bgm._010, "1");
+    assert_eq!(msg.sg1.len(), 1);
+    assert_eq!(msg.sg1[0].dtm.as_ref().unwrap()._010, "1");
+    assert_eq!(msg.sg2.len(), 1);
+    let warnings = ctx.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].expected, "UNH before BGM");
+}
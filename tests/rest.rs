@@ -0,0 +1,42 @@
+mod util;
+
+use edifact_types_macros::{DisplayOuterSegment, Edifact, ParseOuterSegment};
+use util::{ParseContext, Separators};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C506 {
+    pub _010: String,
+    #[edifact(rest)]
+    pub rest: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct RFF {
+    pub _010: Option<C506>,
+    #[edifact(rest)]
+    pub rest: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseOuterSegment)]
+pub struct FTX {
+    pub _010: Option<String>,
+    #[edifact(rest)]
+    pub rest: Vec<String>,
+}
+
+#[test]
+fn keeps_surplus() {
+    let s = "RFF+ON:1:?+x+a:b++c?+d'";
+    let strict = ParseContext::new(Separators::default()).strict();
+    let (_, rff) = RFF::parse_with(s, &strict).unwrap();
+    assert_eq!(rff._010.as_ref().unwrap().rest, vec!["1", "?+x"]);
+    assert_eq!(rff.rest, vec!["a:b", "", "c?+d"]);
+    assert_eq!(format!("{rff}'"), s);
+    let parsed: RFF = s.parse().unwrap();
+    assert_eq!(parsed, rff);
+    let ftx: FTX = "FTX+a+b:c+?+'".parse().unwrap();
+    assert_eq!(ftx.rest, vec!["b:c", "?+"]);
+    assert_eq!(ftx.to_string(), "FTX+a+b:c+?+");
+}
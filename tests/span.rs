@@ -0,0 +1,64 @@
+mod util;
+
+use edifact_types_macros::{Edifact, ParseOuterSegment};
+use util::{Parser, Span};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+    #[edifact(span)]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct LOC {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub loc: LOC,
+    #[edifact(span)]
+    pub span: Span,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    #[edifact(span)]
+    pub span: Span,
+    pub dtm: DTM,
+    pub sg1: Vec<Sg1>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, ParseOuterSegment)]
+pub struct FTX {
+    pub _010: String,
+    #[edifact(span)]
+    pub span: Span,
+}
+
+#[test]
+fn spans() {
+    let s = "DTM+1'\nLOC+a'\r\nDTM+2'\nDTM+3'\nLOC+b'\n";
+    let (_, msg) = Msg::parse(s).unwrap();
+    assert_eq!(msg.span, Span::new(0, s.len() - 1, 1));
+    assert_eq!(msg.dtm.span, Span::new(0, 6, 1));
+    assert_eq!(msg.sg1[0].span, Span::new(7, 28, 2));
+    assert_eq!(
+        &s[msg.sg1[0].dtm[1].span.start..msg.sg1[0].dtm[1].span.end],
+        "DTM+3'"
+    );
+    assert_eq!(msg.sg1[0].dtm[1].span.segment, 4);
+    assert_eq!(msg.sg1[1].span, Span::new(29, 35, 5));
+    // spans are not printed
+    assert_eq!(msg.to_string(), "DTM+1'\nLOC+a'\nDTM+2'\nDTM+3'\nLOC+b'");
+    let (_, dtm) = DTM::parse("DTM+1'  \nLOC+a'").unwrap();
+    assert_eq!(dtm.span, Span::new(0, 6, 1));
+    let ftx: FTX = "FTX+a".parse().unwrap();
+    assert_eq!(ftx._010, "a");
+}
@@ -0,0 +1,113 @@
+//! Segments, groups and messages shared by the tests, derived with the separate derives.
+#![allow(dead_code, non_camel_case_types, clippy::upper_case_acronyms)]
+use crate::util::{ParseError, Parser};
+use edifact_types_macros::*;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum _3035 {
+    #[default]
+    BY,
+    SU,
+}
+impl fmt::Display for _3035 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+impl FromStr for _3035 {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BY" => Ok(_3035::BY),
+            "SU" => Ok(_3035::SU),
+            _ => Err(ParseError {
+                msg: format!("unknown code {s}"),
+            }),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, DisplayInnerSegment, ParseElement, ParseInnerSegment,
+)]
+pub struct C080 {
+    pub _010: String,
+    pub _020: Option<String>,
+    pub _030: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseSegment, ParseOuterSegment,
+)]
+pub struct NAD {
+    #[edifact(code)]
+    pub _010: _3035,
+    pub _020: Option<String>,
+    #[edifact(composite)]
+    pub _030: Option<C080>,
+    pub _040: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseSegment)]
+pub struct DTM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseSegment)]
+pub struct BGM {
+    pub _010: Option<String>,
+    #[edifact(text)]
+    pub _020: Option<An35>,
+}
+
+pub type An35 = String;
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayEdifactSg, ParseSg)]
+pub struct MsgSg1 {
+    pub nad: NAD,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayEdifact, ParseMsg)]
+pub struct MSG {
+    pub bgm: BGM,
+    pub dtm: Option<DTM>,
+    pub sg1: Vec<MsgSg1>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UNA(pub String);
+impl fmt::Display for UNA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl<'a> Parser<&'a str, UNA, crate::util::EdifactError> for UNA {
+    fn parse(input: &'a str) -> nom::IResult<&'a str, UNA, crate::util::EdifactError> {
+        let (rest, _) = nom::bytes::complete::tag("UNA")(input)?;
+        let (rest, c) = nom::bytes::complete::take(6usize)(rest)?;
+        let (rest, _) = nom::character::complete::multispace0(rest)?;
+        Ok((rest, UNA(format!("UNA{c}"))))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, DisplayEdifact, ParseMsg)]
+pub struct Interchange {
+    #[edifact(una)]
+    pub una: Option<UNA>,
+    pub bgm: BGM,
+    pub sg1: Vec<MsgSg1>,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, DisplayOuterSegment, ParseSegment, ParseOuterSegment,
+)]
+pub struct RPT {
+    pub _010: String,
+    #[edifact(max_repeat = 3)]
+    pub _020: Vec<String>,
+    pub _030: Vec<C080>,
+    pub _040: Option<String>,
+}
@@ -0,0 +1,6 @@
+/// Misused attributes are reported at the offending field or struct.
+#[test]
+fn attribute_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,8 @@
+use edifact_types_macros::ParseOuterSegment;
+
+#[derive(ParseOuterSegment)]
+pub struct FTX<'a> {
+    pub _010: &'a str,
+}
+
+fn main() {}
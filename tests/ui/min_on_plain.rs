@@ -0,0 +1,9 @@
+use edifact_types_macros::ParseSg;
+
+#[derive(ParseSg)]
+pub struct Sg1 {
+    #[edifact(min = 1)]
+    pub rff: String,
+}
+
+fn main() {}
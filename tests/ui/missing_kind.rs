@@ -0,0 +1,8 @@
+use edifact_types_macros::Edifact;
+
+#[derive(Edifact)]
+pub struct DTM {
+    pub _010: String,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use edifact_types_macros::ParseSg;
+
+#[derive(ParseSg)]
+pub struct Sg1 {
+    pub rff: String,
+    #[edifact(rest)]
+    pub rest: Vec<String>,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use edifact_types_macros::Edifact;
+
+#[derive(Edifact)]
+#[edifact(segment)]
+pub struct RFF {
+    #[edifact(rest)]
+    pub rest: Vec<String>,
+    pub _010: String,
+}
+
+fn main() {}
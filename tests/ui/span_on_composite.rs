@@ -0,0 +1,12 @@
+use edifact_types_macros::ParseElement;
+
+pub struct Span;
+
+#[derive(ParseElement)]
+pub struct C507 {
+    pub _010: String,
+    #[edifact(span)]
+    pub span: Span,
+}
+
+fn main() {}
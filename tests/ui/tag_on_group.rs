@@ -0,0 +1,9 @@
+use edifact_types_macros::Edifact;
+
+#[derive(Edifact)]
+#[edifact(group, tag = "NAD")]
+pub struct Sg1 {
+    pub nad: String,
+}
+
+fn main() {}
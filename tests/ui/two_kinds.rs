@@ -0,0 +1,9 @@
+use edifact_types_macros::ParseSegment;
+
+#[derive(ParseSegment)]
+pub struct DTM {
+    #[edifact(text, code)]
+    pub _010: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use edifact_types_macros::ParseSegment;
+
+#[derive(ParseSegment)]
+pub struct FTX<'a, 'b> {
+    pub _010: &'a str,
+    pub _020: &'b str,
+}
+
+fn main() {}
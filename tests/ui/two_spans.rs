@@ -0,0 +1,14 @@
+use edifact_types_macros::ParseSegment;
+
+pub struct Span;
+
+#[derive(ParseSegment)]
+pub struct DTM {
+    pub _010: String,
+    #[edifact(span)]
+    pub span: Span,
+    #[edifact(span)]
+    pub other: Span,
+}
+
+fn main() {}
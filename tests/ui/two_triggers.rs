@@ -0,0 +1,11 @@
+use edifact_types_macros::ParseSg;
+
+#[derive(ParseSg)]
+pub struct Sg1 {
+    #[edifact(trigger)]
+    pub rff: String,
+    #[edifact(trigger)]
+    pub dtm: String,
+}
+
+fn main() {}
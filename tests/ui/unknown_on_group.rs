@@ -0,0 +1,9 @@
+use edifact_types_macros::Edifact;
+
+#[derive(Edifact)]
+#[edifact(group, unknown = "skip")]
+pub struct Sg1 {
+    pub rff: String,
+}
+
+fn main() {}
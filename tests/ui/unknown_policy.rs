@@ -0,0 +1,9 @@
+use edifact_types_macros::Edifact;
+
+#[derive(Edifact)]
+#[edifact(message, unknown = "keep")]
+pub struct Msg {
+    pub bgm: String,
+}
+
+fn main() {}
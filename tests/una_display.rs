@@ -0,0 +1,46 @@
+mod types;
+mod util;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use types::{Interchange, UNA};
+use util::Parser;
+
+/// Counts allocations, this binary has a single test so nothing else runs meanwhile.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Discards the output.
+struct Sink;
+
+impl Write for Sink {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+#[test]
+fn una_separators_without_allocating() {
+    let s = "UNA|=.# ~\nBGM=a#~b~";
+    let (_, msg) = Interchange::parse(s).unwrap();
+    assert_eq!(msg.una, Some(UNA("UNA|=.# ~".into())));
+    assert_eq!(msg.to_string(), s);
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    write!(Sink, "{msg}").unwrap();
+    assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), before);
+}
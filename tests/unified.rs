@@ -0,0 +1,129 @@
+mod types;
+mod util;
+
+use edifact_types_macros::Edifact;
+use types::_3035;
+use util::Parser;
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(composite)]
+pub struct C082 {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD {
+    #[edifact(code)]
+    pub _010: _3035,
+    pub _020: Option<C082>,
+    pub _030: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg2 {
+    pub nad: NAD,
+    pub bgm: Option<BGM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub bgm: BGM,
+    pub sg2: Vec<Sg2>,
+}
+
+#[test]
+fn unified_roundtrip() {
+    let s = "BGM+a?'b'\nNAD+BY+x:y+1*2'\nNAD+SU'\nBGM+c'";
+    let msg: Msg = s.parse().unwrap();
+    assert_eq!(msg.sg2.len(), 2);
+    assert_eq!(msg.to_string(), s);
+    assert_eq!(Msg::parse(s).unwrap().1, msg);
+    let nad: NAD = "NAD+BY+x:y'".parse().unwrap();
+    assert_eq!(nad.to_string().parse::<NAD>().unwrap(), nad);
+    let c: C082 = "x:y?:z".parse().unwrap();
+    assert_eq!(c._020.as_deref(), Some("y:z"));
+    assert_eq!(c.to_string(), "x:y?:z");
+    let e = "NAD+BY'DTM+1'".parse::<NAD>().unwrap_err();
+    assert_eq!(e.path(), "NAD");
+    assert_eq!(e.offset, Some(7));
+    let e = s.replace("SU", "XX").parse::<Msg>().unwrap_err();
+    assert_eq!(e.path(), "MSG/SG2/NAD/3035");
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment, tag = "NAD")]
+pub struct NadBuyer {
+    #[edifact(code)]
+    pub _010: _3035,
+    pub _020: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    edifact_types_macros::DisplayOuterSegment,
+    edifact_types_macros::ParseSegment,
+    edifact_types_macros::ParseOuterSegment,
+)]
+#[edifact(tag = "NAD")]
+pub struct NadSeller {
+    pub _010: Option<String>,
+    pub _020: Option<String>,
+}
+
+#[test]
+fn explicit_tag() {
+    let b: NadBuyer = "NAD+BY+x'".parse().unwrap();
+    assert_eq!(b.to_string(), "NAD+BY+x");
+    let (_, s) = NadSeller::parse("NAD+SU'").unwrap();
+    assert_eq!(s._010.as_deref(), Some("SU"));
+    assert_eq!(
+        "NAD+SU+y".parse::<NadSeller>().unwrap()._020.as_deref(),
+        Some("y")
+    );
+    assert_eq!(NadSeller::default().to_string(), "");
+    assert_eq!(
+        NadSeller {
+            _010: None,
+            _020: Some("".into())
+        }
+        .to_string(),
+        ""
+    );
+    let e = "DTM+1'".parse::<NadBuyer>().unwrap_err();
+    assert_eq!(e.path(), "NAD");
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct FTX {
+    pub _010: String,
+    pub _020: Option<String>,
+}
+
+#[test]
+fn released_terminator_round_trips() {
+    let ftx = FTX {
+        _010: "AAI".into(),
+        _020: Some("Shipper Ltd'".into()),
+    };
+    let s = ftx.to_string();
+    assert_eq!(s, "FTX+AAI+Shipper Ltd?'");
+    assert_eq!(s.parse::<FTX>().unwrap(), ftx);
+    // a released release character leaves the terminator unreleased
+    let ftx: FTX = "FTX+AAI+a??'".parse().unwrap();
+    assert_eq!(ftx._020.as_deref(), Some("a?"));
+    assert_eq!(ftx.to_string().parse::<FTX>().unwrap(), ftx);
+}
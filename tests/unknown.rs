@@ -0,0 +1,112 @@
+mod util;
+
+use edifact_types_macros::Edifact;
+use util::{Parser, RawSegment};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct DTM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct LOC {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(group)]
+pub struct Sg1 {
+    pub loc: LOC,
+    pub dtm: Vec<DTM>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message, unknown = "reject")]
+pub struct Rejecting {
+    pub bgm: BGM,
+    pub sg1: Vec<Sg1>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message, unknown = "skip")]
+pub struct Skipping {
+    pub bgm: BGM,
+    pub sg1: Vec<Sg1>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Capturing<'a> {
+    pub bgm: BGM,
+    pub sg1: Vec<Sg1>,
+    #[edifact(unknown)]
+    pub unknown: Vec<RawSegment<'a>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Stopping {
+    pub bgm: BGM,
+    pub sg1: Vec<Sg1>,
+}
+
+const INPUT: &str = "BGM+1'XYZ+a'LOC+a'ABC'DTM+1'LOC+b'UNZ+1'";
+
+#[test]
+fn reject() {
+    let err = Rejecting::parse(INPUT).unwrap_err();
+    let nom::Err::Failure(e) = err else {
+        panic!("{err:?}")
+    };
+    assert_eq!(e.path(), "REJECTING");
+    assert_eq!(e.expected, "known segment");
+    assert_eq!(e.found, "XYZ");
+    assert_eq!(e.segment, Some(2));
+    let (rest, msg) = Rejecting::parse("BGM+1'LOC+a'UNZ+1'").unwrap();
+    assert_eq!(rest, "UNZ+1'");
+    assert_eq!(msg.sg1.len(), 1);
+}
+
+#[test]
+fn skip() {
+    let (msg, diagnostics) = Skipping::parse_recovering("BGM+1'XYZ+a'LOC+a'ABC'DTM+1'LOC+b'");
+    let msg = msg.unwrap();
+    assert_eq!(msg.sg1.len(), 2);
+    assert_eq!(msg.sg1[0].dtm.len(), 1);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].found, "XYZ");
+    assert_eq!(diagnostics[1].found, "ABC");
+    assert_eq!(diagnostics[1].segment, Some(4));
+    let (rest, _) = Skipping::parse(INPUT).unwrap();
+    assert_eq!(rest, "UNZ+1'");
+}
+
+#[test]
+fn capture() {
+    let (rest, msg) = Capturing::parse(INPUT).unwrap();
+    assert_eq!(rest, "UNZ+1'");
+    assert_eq!(msg.sg1.len(), 2);
+    let tags: Vec<_> = msg.unknown.iter().map(|t| t.tag).collect();
+    assert_eq!(tags, ["XYZ", "ABC"]);
+    assert_eq!(msg.unknown[0].raw, "XYZ+a'");
+    // not printed
+    assert_eq!(msg.to_string(), "BGM+1'\nLOC+a'\nDTM+1'\nLOC+b'");
+    let (_, msg) = Capturing::parse("BGM+1'LOC+a'").unwrap();
+    assert!(msg.unknown.is_empty());
+}
+
+#[test]
+fn stop() {
+    let (rest, msg) = Stopping::parse(INPUT).unwrap();
+    assert_eq!(rest, &INPUT[6..]);
+    assert!(msg.sg1.is_empty());
+    assert!(DTM::knows_tag("DTM") && Sg1::knows_tag("DTM") && !Stopping::knows_tag("XYZ"));
+}
@@ -0,0 +1,510 @@
+//! Minimal `util` runtime implementing the contract from the README, as a host crate would.
+#![allow(dead_code)]
+
+use nom::IResult;
+use std::fmt;
+
+pub trait Parser<I, O, E> {
+    fn parse(input: I) -> IResult<I, O, E>;
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub msg: String,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+pub fn clean_num(x: &str) -> &str {
+    x
+}
+
+pub fn parse_line<'a>(input: &'a str, key: &str) -> IResult<&'a str, Vec<&'a str>> {
+    let (rest, _) = nom::bytes::complete::tag(key)(input)?;
+    let (rest, line) = nom::bytes::complete::take_until("'")(rest)?;
+    let (rest, _) = nom::bytes::complete::tag("'")(rest)?;
+    let (rest, _) = nom::character::complete::multispace0(rest)?;
+    let mut vars: Vec<&str> = line.split('+').collect();
+    vars.remove(0);
+    Ok((rest, vars))
+}
+
+pub fn parse_colon_section(input: &str) -> IResult<&str, Vec<&str>> {
+    Ok(("", input.split(':').collect()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Separators {
+    pub component: char,
+    pub element: char,
+    pub decimal: char,
+    pub release: char,
+    pub repetition: char,
+    pub segment: char,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators {
+            component: ':',
+            element: '+',
+            decimal: '.',
+            release: '?',
+            repetition: '*',
+            segment: '\'',
+        }
+    }
+}
+
+impl Separators {
+    pub fn from_una(input: &str) -> Option<Separators> {
+        let rest = input.strip_prefix("UNA")?;
+        let mut c = [' '; 6];
+        let mut chars = rest.chars();
+        for slot in &mut c {
+            *slot = chars.next()?;
+        }
+        Some(Separators {
+            component: c[0],
+            element: c[1],
+            decimal: c[2],
+            release: c[3],
+            repetition: if c[4] == ' ' { '*' } else { c[4] },
+            segment: c[5],
+        })
+    }
+}
+
+pub fn split_released<'a>(input: &'a str, sep: char, seps: &Separators) -> Vec<&'a str> {
+    let mut out = vec![];
+    let mut start = 0;
+    let mut released = false;
+    for (i, c) in input.char_indices() {
+        if released {
+            released = false;
+        } else if c == seps.release {
+            released = true;
+        } else if c == sep {
+            out.push(&input[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    out.push(&input[start..]);
+    out
+}
+
+pub fn unescape(value: &str, seps: &Separators) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut released = false;
+    for c in value.chars() {
+        if !released && c == seps.release {
+            released = true;
+            continue;
+        }
+        released = false;
+        out.push(c);
+    }
+    out
+}
+
+pub fn parse_line_released<'a>(
+    input: &'a str,
+    key: &str,
+    seps: &Separators,
+) -> IResult<&'a str, Vec<&'a str>> {
+    let (rest, _) = nom::bytes::complete::tag(key)(input)?;
+    let mut end = None;
+    let mut released = false;
+    for (i, c) in rest.char_indices() {
+        if released {
+            released = false;
+        } else if c == seps.release {
+            released = true;
+        } else if c == seps.segment {
+            end = Some(i);
+            break;
+        }
+    }
+    let Some(end) = end else {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            rest,
+            nom::error::ErrorKind::TakeUntil,
+        )));
+    };
+    let line = &rest[..end];
+    if !line.is_empty() && !line.starts_with(seps.element) {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )));
+    }
+    let rest = &rest[end + seps.segment.len_utf8()..];
+    let (rest, _) = nom::character::complete::multispace0(rest)?;
+    let mut vars = split_released(line, seps.element, seps);
+    vars.remove(0);
+    Ok((rest, vars))
+}
+
+/// Boxed, so results carrying it stay small.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EdifactError(Box<ErrorInfo>);
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ErrorInfo {
+    pub path: Vec<&'static str>,
+    pub segment: Option<usize>,
+    pub element: Option<usize>,
+    pub component: Option<usize>,
+    pub offset: Option<usize>,
+    pub expected: String,
+    pub found: String,
+    at: usize,
+    len: usize,
+}
+
+impl std::ops::Deref for EdifactError {
+    type Target = ErrorInfo;
+    fn deref(&self) -> &ErrorInfo {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for EdifactError {
+    fn deref_mut(&mut self) -> &mut ErrorInfo {
+        &mut self.0
+    }
+}
+
+impl EdifactError {
+    pub fn new(expected: &str, at: &str) -> Self {
+        EdifactError(Box::new(ErrorInfo {
+            expected: expected.to_string(),
+            at: at.as_ptr() as usize,
+            len: at.len(),
+            ..Default::default()
+        }))
+    }
+    pub fn within(mut self, label: &'static str) -> Self {
+        self.path.insert(0, label);
+        self
+    }
+    pub fn at_element(mut self, idx: usize) -> Self {
+        self.element = Some(idx);
+        self
+    }
+    pub fn at_component(mut self, idx: usize) -> Self {
+        self.component = Some(idx);
+        self
+    }
+    pub fn locate(mut self, input: &str, seps: &Separators) -> Self {
+        let start = input.as_ptr() as usize;
+        if self.at >= start && self.at + self.len <= start + input.len() {
+            let offset = self.at - start;
+            self.offset = Some(offset);
+            // at most the rest of the segment, a failed tag would point at all the input left
+            let found = &input[offset..offset + self.len];
+            self.found = found
+                .split(seps.segment)
+                .next()
+                .unwrap_or(found)
+                .to_string();
+            self.segment = Some(split_released(&input[..offset], seps.segment, seps).len());
+        }
+        self
+    }
+    /// Moves an error pointing into `from` to the same place in `to`, `map` translates byte offsets.
+    pub fn relocate(mut self, from: &str, to: &str, map: impl Fn(usize) -> usize) -> Self {
+        let start = from.as_ptr() as usize;
+        if self.at >= start && self.at + self.len <= start + from.len() {
+            let offset = self.at - start;
+            let (begin, end) = (map(offset), map(offset + self.len));
+            self.at = to.as_ptr() as usize + begin;
+            self.len = end - begin;
+        }
+        self
+    }
+    pub fn path(&self) -> String {
+        self.path.join("/")
+    }
+}
+
+impl fmt::Display for EdifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, found {:?}",
+            self.path(),
+            self.expected,
+            self.found
+        )
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a str> for EdifactError {
+    fn from_error_kind(input: &'a str, kind: nom::error::ErrorKind) -> Self {
+        EdifactError::new(kind.description(), input)
+    }
+    fn append(_: &'a str, _: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+pub trait ParseValue<'a>: Sized {
+    fn parse_value(raw: &'a str, ctx: &ParseContext) -> Result<Self, EdifactError>;
+}
+
+pub trait DisplayValue {
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>, seps: &Separators) -> fmt::Result;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub segment: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, segment: usize) -> Self {
+        Span {
+            start,
+            end,
+            segment,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawSegment<'a> {
+    pub tag: &'a str,
+    pub elements: Vec<&'a str>,
+    pub raw: &'a str,
+    pub span: Span,
+}
+
+/// Splits `input` into segments, returns the unterminated rest.
+pub fn tokenize<'a>(input: &'a str, ctx: &ParseContext) -> (Vec<RawSegment<'a>>, &'a str) {
+    let seps: &Separators = ctx;
+    let space = |c: char| ctx.whitespace.contains(c);
+    let mut out = vec![];
+    let mut rest = input.trim_start_matches(space);
+    if rest.starts_with("UNA") && rest.len() >= 9 {
+        let start = rest.as_ptr() as usize - input.as_ptr() as usize;
+        out.push(RawSegment {
+            tag: "UNA",
+            elements: vec![],
+            raw: &rest[..9],
+            span: Span::new(start, start + 9, 1),
+        });
+        rest = rest[9..].trim_start_matches(space);
+    }
+    loop {
+        let mut end = None;
+        let mut released = false;
+        for (i, c) in rest.char_indices() {
+            if released {
+                released = false;
+            } else if c == seps.release {
+                released = true;
+            } else if c == seps.segment {
+                end = Some(i);
+                break;
+            }
+        }
+        let Some(end) = end else {
+            return (out, rest);
+        };
+        let line = &rest[..end];
+        let mut elements = split_released(line, seps.element, seps);
+        let tag = elements.remove(0);
+        let start = rest.as_ptr() as usize - input.as_ptr() as usize;
+        let len = end + seps.segment.len_utf8();
+        let segment = out.len() + 1;
+        out.push(RawSegment {
+            tag,
+            elements,
+            raw: &rest[..len],
+            span: Span::new(start, start + len, segment),
+        });
+        rest = rest[end + seps.segment.len_utf8()..].trim_start_matches(space);
+    }
+}
+
+/// Displays whatever the closure writes.
+pub struct DisplayFn<F>(pub F);
+
+impl<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result> fmt::Display for DisplayFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
+/// Displays a value with its service characters released.
+pub struct Escaped<'s, T>(pub T, pub &'s Separators);
+
+impl<T: fmt::Display> fmt::Display for Escaped<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct W<'a, 'b, 's>(&'a mut fmt::Formatter<'b>, &'s Separators);
+        impl fmt::Write for W<'_, '_, '_> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                let seps = self.1;
+                let mut start = 0;
+                for (i, c) in s.char_indices() {
+                    if c == seps.component
+                        || c == seps.element
+                        || c == seps.segment
+                        || c == seps.release
+                        || c == seps.repetition
+                    {
+                        self.0.write_str(&s[start..i])?;
+                        self.0.write_char(seps.release)?;
+                        start = i;
+                    }
+                }
+                self.0.write_str(&s[start..])
+            }
+        }
+        fmt::Write::write_fmt(&mut W(f, self.1), format_args!("{}", self.0))
+    }
+}
+
+/// Holds back separators until something non-empty follows, trailing ones are never written.
+pub struct Separated<'p, W> {
+    inner: W,
+    prefix: &'p str,
+    sep: char,
+    lines: bool,
+    pending: usize,
+    written: bool,
+}
+
+impl<'p, W: fmt::Write> Separated<'p, W> {
+    pub fn new(inner: W, prefix: &'p str, sep: char) -> Self {
+        Separated {
+            inner,
+            prefix,
+            sep,
+            lines: false,
+            pending: 0,
+            written: false,
+        }
+    }
+    pub fn lines(inner: W, sep: char) -> Self {
+        Separated {
+            inner,
+            prefix: "",
+            sep,
+            lines: true,
+            pending: 0,
+            written: false,
+        }
+    }
+    pub fn separate(&mut self) {
+        if !self.lines {
+            self.pending += 1;
+        } else if self.written {
+            self.pending = 1;
+        }
+    }
+    pub fn written(&self) -> bool {
+        self.written
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for Separated<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if !self.written {
+            self.inner.write_str(self.prefix)?;
+            self.written = true;
+        }
+        for _ in 0..self.pending {
+            self.inner.write_char(self.sep)?;
+            if self.lines {
+                self.inner.write_char('\n')?;
+            }
+        }
+        self.pending = 0;
+        self.inner.write_str(s)
+    }
+}
+
+/// Separators and options of a parse call.
+#[derive(Debug, Clone, Default)]
+pub struct ParseContext {
+    pub seps: Separators,
+    pub strict: bool,
+    pub recover: bool,
+    pub reorder: bool,
+    pub wrapped: bool,
+    pub whitespace: &'static str,
+    diagnostics: std::cell::RefCell<Vec<EdifactError>>,
+    warnings: std::cell::RefCell<Vec<EdifactError>>,
+}
+
+impl ParseContext {
+    pub fn new(seps: Separators) -> Self {
+        ParseContext {
+            seps,
+            strict: false,
+            recover: false,
+            reorder: false,
+            wrapped: false,
+            whitespace: " \t\r\n",
+            diagnostics: Default::default(),
+            warnings: Default::default(),
+        }
+    }
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+    pub fn recover(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+    pub fn wrapped(mut self) -> Self {
+        self.wrapped = true;
+        self
+    }
+    pub fn whitespace(mut self, whitespace: &'static str) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+    pub fn reorder(mut self) -> Self {
+        self.reorder = true;
+        self
+    }
+    pub fn warn(&self, e: EdifactError) {
+        self.warnings.borrow_mut().push(e);
+    }
+    pub fn take_warnings(&self) -> Vec<EdifactError> {
+        self.warnings.take()
+    }
+    pub fn report(&self, e: EdifactError) {
+        self.diagnostics.borrow_mut().push(e);
+    }
+    pub fn mark(&self) -> usize {
+        self.diagnostics.borrow().len()
+    }
+    pub fn within_since(&self, mark: usize, label: &'static str) {
+        for e in self.diagnostics.borrow_mut()[mark..].iter_mut() {
+            e.path.insert(0, label);
+        }
+    }
+    pub fn take_diagnostics(&self) -> Vec<EdifactError> {
+        self.diagnostics.take()
+    }
+}
+
+impl std::ops::Deref for ParseContext {
+    type Target = Separators;
+    fn deref(&self) -> &Separators {
+        &self.seps
+    }
+}
@@ -0,0 +1,157 @@
+mod util;
+
+use edifact_types_macros::Edifact;
+use util::{ParseContext, Parser, RawSegment, Separators, Span};
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct BGM {
+    pub _010: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct NAD {
+    pub _010: String,
+    pub _020: String,
+    #[edifact(span)]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Msg {
+    pub bgm: BGM,
+    pub nad: Vec<NAD>,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(segment)]
+pub struct FTX<'a> {
+    pub _010: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Edifact)]
+#[edifact(message)]
+pub struct Notes<'a> {
+    pub ftx: Vec<FTX<'a>>,
+    #[edifact(unknown)]
+    pub unknown: Vec<RawSegment<'a>>,
+}
+
+#[test]
+fn crlf_and_trailing_spaces() {
+    let s = "BGM+1'  \r\nNAD+BY+a'\t\r\nNAD+SU+b' \r\n";
+    let msg: Msg = s.parse().unwrap();
+    assert_eq!(msg.nad.len(), 2);
+    assert_eq!(msg.nad[1].span, Span::new(22, 31, 3));
+    let (rest, nad) = NAD::parse("\r\n  NAD+BY+a' \r\nNAD").unwrap();
+    assert_eq!(rest, "NAD");
+    assert_eq!(nad.span, Span::new(4, 13, 1));
+}
+
+#[test]
+fn configured_whitespace() {
+    let ctx = ParseContext::new(Separators::default()).whitespace("\n~");
+    let (rest, msg) = Msg::parse_with("BGM+1'~~\nNAD+BY+a'~", &ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(msg.nad.len(), 1);
+    // spaces are not whitespace any more
+    let (rest, msg) = Msg::parse_with("BGM+1' NAD+BY+a'", &ctx).unwrap();
+    assert_eq!(rest, " NAD+BY+a'");
+    assert!(msg.nad.is_empty());
+}
+
+#[test]
+fn wrapped_lines() {
+    let s = "BGM+1'NAD+BY+abc\r\ndef'NA\nD+SU+x'\nBGM+2'";
+    let ctx = ParseContext::new(Separators::default()).wrapped();
+    let (rest, msg) = Msg::parse_with(s, &ctx).unwrap();
+    assert_eq!(rest, "BGM+2'");
+    assert_eq!(msg.nad[0]._020, "abcdef");
+    assert_eq!(msg.nad[1]._010, "SU");
+    let (rest, nad) = NAD::parse_with("NAD+BY\n+a'\nBGM", &ctx).unwrap();
+    assert_eq!(rest, "BGM");
+    assert_eq!(nad._020, "a");
+    // without the option the line break stays in the value
+    let (_, msg) = Msg::parse_with(s, &ParseContext::new(Separators::default())).unwrap();
+    assert_eq!(msg.nad[0]._020, "abc\r\ndef");
+    // borrowed values cannot be rejoined
+    assert!(FTX::parse_with("FTX+a\nb'", &ctx).is_err());
+    assert!(FTX::parse_with("FTX+ab'", &ctx).is_ok());
+}
+
+#[test]
+fn wrapped_positions() {
+    let ctx = ParseContext::new(Separators::default()).wrapped();
+    // spans and errors count in the input, not in the rejoined text
+    let s = "BGM+1'\r\nNAD+BY+a\r\nbc'\r\nNAD+SU+x'";
+    let (_, msg) = Msg::parse_with(s, &ctx).unwrap();
+    assert_eq!(msg.nad[0]._020, "abc");
+    assert_eq!(msg.nad[0].span, Span::new(8, 21, 2));
+    assert_eq!(msg.nad[1].span, Span::new(23, 32, 3));
+    let (_, nad) = NAD::parse_with("\nNAD+B\nY+a'\n", &ctx).unwrap();
+    assert_eq!(nad.span, Span::new(1, 11, 1));
+    let s = "BGM+1'\nNAD+B\nY'\nNAD+SU'";
+    let e = match Msg::parse_with(s, &ctx) {
+        Err(nom::Err::Failure(e)) => e.locate(s, &ctx),
+        r => panic!("{r:?}"),
+    };
+    assert_eq!(e.path(), "NAD/020");
+    assert_eq!(e.segment, Some(2));
+    assert_eq!(&s[e.offset.unwrap()..], "'\nNAD+SU'");
+    let s = "NAD+B\nY'";
+    let e = match NAD::parse_with(s, &ctx) {
+        Err(nom::Err::Failure(e)) => e.locate(s, &ctx),
+        r => panic!("{r:?}"),
+    };
+    assert_eq!(e.offset, Some(7));
+    // so do diagnostics
+    let ctx = ParseContext::new(Separators::default()).wrapped().recover();
+    let s = "BGM+1'\nNAD+B\nY'\nNAD+SU+x'";
+    let (_, msg) = Msg::parse_with(s, &ctx).unwrap();
+    assert_eq!(msg.nad.len(), 1);
+    let diagnostics = ctx.take_diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    let d = diagnostics[0].clone().locate(s, &ctx);
+    assert_eq!((d.segment, d.offset), (Some(2), Some(14)));
+}
+
+#[test]
+fn wrapped_borrowed() {
+    let ctx = ParseContext::new(Separators::default()).wrapped();
+    // line breaks between segments are no problem for borrowing structs
+    let s = "FTX+a'\r\nXYZ+1'\r\nFTX+b'\r\n";
+    let (rest, notes) = Notes::parse_with(s, &ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(notes.ftx.len(), 2);
+    assert_eq!(notes.unknown[0].raw, "XYZ+1'");
+    let s = "FTX+a'\r\nFTX+b\r\nc'";
+    let e = match Notes::parse_with(s, &ctx) {
+        Err(nom::Err::Failure(e)) => e.locate(s, &ctx),
+        r => panic!("{r:?}"),
+    };
+    assert_eq!(e.expected, "segment without line breaks");
+    assert_eq!(e.segment, Some(2));
+}
+
+#[test]
+fn wrapped_after_release() {
+    // a line break right after the release character does not end its release
+    let ctx = ParseContext::new(Separators::default()).wrapped();
+    let s = "BGM+1'NAD+BY+ab?\r\n'c'NAD+SU+x'";
+    let (rest, msg) = Msg::parse_with(s, &ctx).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(msg.nad.len(), 2);
+    assert_eq!(msg.nad[0]._020, "ab'c");
+    assert_eq!(msg.nad[0].span, Span::new(6, 21, 2));
+    let (rest, nad) = NAD::parse_with("NAD+BY+ab?\r\n'c'NAD", &ctx).unwrap();
+    assert_eq!(rest, "NAD");
+    assert_eq!(nad._020, "ab'c");
+    // the released terminator leaves the segment unterminated
+    let (rest, msg) = Msg::parse_with("BGM+1'NAD+BY+a?\n'\nBGM", &ctx).unwrap();
+    assert_eq!(rest, "NAD+BY+a?\n'\nBGM");
+    assert!(msg.nad.is_empty());
+    assert!(Notes::parse_with("FTX+a?\n'b'", &ctx).is_err());
+    assert!(FTX::parse_with("FTX+a?\n'b'", &ctx).is_err());
+}